@@ -0,0 +1,282 @@
+//! The [`FileManager`] trait abstracts over the different container implementations, so code can
+//! be written once and run against the dll as well as the native backends.
+//!
+//! Paths are either relative to the current directory or absolute when they start with a
+//! separator, both `/` and `\` are accepted. Lookups are case-insensitive. The native backends
+//! resolve them with [`ArchivePath`], which also rejects names that do not fit into an entry.
+
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, FileTime};
+
+mod extract;
+mod glob;
+mod host;
+mod index;
+mod memory;
+mod path;
+mod walk;
+
+pub use self::extract::{Extract, ExtractAction, ExtractProgress, ExtractSummary, Overwrite};
+pub use self::glob::Glob;
+pub use self::host::{HostFileManager, HostReadDir};
+pub use self::index::{ContainerStamp, Index, IndexEntry, NodeId};
+pub use self::memory::{MemoryArchive, MemoryFileMut};
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
+pub(crate) use self::path::check_names;
+pub use self::path::ArchivePath;
+pub(crate) use self::path::{check_name, check_stored_name};
+pub use self::walk::{Walk, WalkEvent};
+
+/// An entry of a directory, returned by [`FileManager::read_dir`] and
+/// [`FileManager::find_files`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Path of the entry, the directory path passed to `read_dir` joined with its name.
+    pub path: String,
+    pub name: String,
+    pub kind: EntryKind,
+    /// Size of the file in bytes, zero for directories.
+    pub size: u64,
+    pub create_time: FileTime,
+    pub modify_time: FileTime,
+    pub access_time: FileTime,
+    /// Offset of the file's payload or the directory's first block inside the container, if the
+    /// backend stores its entries in one.
+    pub position: Option<u64>,
+}
+
+impl DirEntry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+}
+
+pub trait FileManager {
+    /// Handle returned by [`FileManager::open_file`].
+    type File<'a>: Read + Seek
+    where
+        Self: 'a;
+    /// Handle returned by [`FileManager::create_file`], its contents are only guaranteed to be
+    /// stored after it has been flushed or dropped.
+    type FileMut<'a>: Write + Seek
+    where
+        Self: 'a;
+    /// Iterator returned by [`FileManager::read_dir`].
+    type ReadDir<'a>: Iterator<Item = Result<DirEntry>>
+    where
+        Self: 'a;
+
+    /// Opens an existing file for reading.
+    fn open_file(&self, path: &str) -> Result<Self::File<'_>>;
+
+    /// Creates a file, truncating it if it already exists.
+    fn create_file(&self, path: &str) -> Result<Self::FileMut<'_>>;
+
+    fn delete_file(&self, path: &str) -> Result<()>;
+
+    fn create_directory(&self, path: &str) -> Result<()>;
+
+    /// Deletes an empty directory.
+    fn delete_directory(&self, path: &str) -> Result<()>;
+
+    /// Changes the current working directory.
+    fn change_directory(&mut self, path: &str) -> Result<()>;
+
+    /// Resets the current working directory to the root.
+    fn reset_directory(&mut self) -> Result<()>;
+
+    /// Returns the current working directory.
+    fn current_directory(&self) -> Result<String>;
+
+    /// Returns true if a file or directory exists at the given path.
+    fn file_exists(&self, path: &str) -> Result<bool>;
+
+    /// Returns an iterator over the entries of a directory, like [`std::fs::read_dir`]. The `.`
+    /// and `..` entries are skipped.
+    fn read_dir(&self, path: &str) -> Result<Self::ReadDir<'_>>;
+
+    /// Reads the whole file at the given path.
+    fn bytes(&self, path: &str) -> Result<Vec<u8>> {
+        let mut file = self.open_file(path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut buf = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+        file.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Returns the entries matching the pattern, whose last component may contain `*` and `?`
+    /// wildcards, for example `Media/*.txt`. The wildcards behave as described for [`Glob`].
+    /// Use [`FileManager::glob`] to search more than one directory.
+    fn find_files(&self, pattern: &str) -> Result<Vec<DirEntry>> {
+        let (dir, name_pattern) = split_pattern(pattern);
+        filter_entries(self.read_dir(dir)?, name_pattern)
+    }
+
+    /// Returns a recursive iterator over the tree below `root`, see [`Walk`].
+    fn walk(&self, root: &str) -> Walk<'_, Self>
+    where
+        Self: Sized,
+    {
+        Walk::new(self, root)
+    }
+
+    /// Copies the tree below `root` into the directory `target` on the host filesystem, see
+    /// [`Extract`] for the available options.
+    fn extract<P: AsRef<Path>>(&self, root: &str, target: P) -> Extract<'_, Self>
+    where
+        Self: Sized,
+    {
+        Extract::new(self, root, target.as_ref())
+    }
+
+    /// Returns the files and directories whose path matches the pattern, sorted by path, for
+    /// example `Media/**/*.txt`. Only the directories that could contain a match are read. A
+    /// pattern whose leading directory does not exist has no matches.
+    fn glob(&self, pattern: &str) -> Result<Vec<DirEntry>>
+    where
+        Self: Sized,
+    {
+        let glob = Glob::new(pattern);
+        let prefix = glob.literal_prefix();
+        let root = if glob.is_literal() {
+            // the pattern names a single entry, which is found in its parent directory
+            match prefix.rfind('/') {
+                Some(0) => "/",
+                Some(idx) => &prefix[..idx],
+                None => "",
+            }
+        } else {
+            &prefix
+        };
+        match self.read_dir(root) {
+            Ok(_) => {}
+            Err(Error::NotFound(_) | Error::NotADirectory(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        }
+
+        let mut matches = Vec::new();
+        let mut files = Vec::new();
+        let walk = self.walk(root).prune(|dir| {
+            if glob.is_match(&dir.path) {
+                matches.push(dir.clone());
+            }
+            !glob.could_match_below(&dir.path)
+        });
+        for event in walk {
+            if let WalkEvent::File(file) = event? {
+                if glob.is_match(&file.path) {
+                    files.push(file);
+                }
+            }
+        }
+        matches.append(&mut files);
+        matches.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(matches)
+    }
+
+    /// Copies `src_dir/file_name` from the host filesystem to `dst_dir/file_name` in the
+    /// container.
+    fn import_file(&self, src_dir: &Path, dst_dir: &str, file_name: &str) -> Result<()> {
+        let mut src = fs::File::open(src_dir.join(file_name))?;
+        let mut dst = self.create_file(&join(dst_dir, file_name))?;
+        io::copy(&mut src, &mut dst)?;
+        dst.flush()?;
+        Ok(())
+    }
+
+    /// Recursively copies `src_dir/dir_name` from the host filesystem to `dst_dir/dir_name` in
+    /// the container.
+    fn import_directory(&self, src_dir: &Path, dst_dir: &str, dir_name: &str) -> Result<()> {
+        let src = src_dir.join(dir_name);
+        let dst = join(dst_dir, dir_name);
+        self.create_directory(&dst)?;
+        for entry in fs::read_dir(&src)? {
+            let entry = entry?;
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| Error::InvalidPath(name.to_string_lossy().into_owned()))?;
+            if entry.file_type()?.is_dir() {
+                self.import_directory(&src, &dst, &name)?;
+            } else {
+                self.import_file(&src, &dst, &name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `src_dir/file_name` from the container to `dst_dir/file_name` on the host
+    /// filesystem.
+    fn export_file(&self, src_dir: &str, dst_dir: &Path, file_name: &str) -> Result<()> {
+        let mut src = self.open_file(&join(src_dir, file_name))?;
+        fs::create_dir_all(dst_dir)?;
+        let mut dst = fs::File::create(dst_dir.join(file_name))?;
+        io::copy(&mut src, &mut dst)?;
+        Ok(())
+    }
+
+    /// Recursively copies `src_dir/dir_name` from the container to `dst_dir/dir_name` on the host
+    /// filesystem.
+    fn export_directory(&self, src_dir: &str, dst_dir: &Path, dir_name: &str) -> Result<()> {
+        let src = join(src_dir, dir_name);
+        let dst = dst_dir.join(dir_name);
+        fs::create_dir_all(&dst)?;
+        for entry in self.read_dir(&src)? {
+            let entry = entry?;
+            if entry.is_dir() {
+                self.export_directory(&src, &dst, &entry.name)?;
+            } else {
+                self.export_file(&src, &dst, &entry.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends `name` to the archive path `dir`.
+pub(crate) fn join(dir: &str, name: &str) -> String {
+    match dir {
+        "" => name.to_owned(),
+        _ if dir.ends_with(['/', '\\']) => format!("{dir}{name}"),
+        _ => format!("{dir}/{name}"),
+    }
+}
+
+/// Splits a path into its components, both `/` and `\` are separators.
+pub(crate) fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\']).filter(|c| !c.is_empty())
+}
+
+/// Resolves a path against the current directory into its normalized components.
+pub(crate) fn resolve(cwd: &ArchivePath, path: &str) -> Result<Vec<String>> {
+    Ok(cwd.resolve(path)?.into_components())
+}
+
+/// Splits a search pattern into the directory to search and the pattern for the names in it.
+pub(crate) fn split_pattern(pattern: &str) -> (&str, &str) {
+    let (dir, name_pattern) = match pattern.rfind(['/', '\\']) {
+        Some(idx) => (&pattern[..=idx], &pattern[idx + 1..]),
+        None => ("", pattern),
+    };
+    (dir, if name_pattern.is_empty() { "*" } else { name_pattern })
+}
+
+/// Collects the entries whose names match the pattern.
+pub(crate) fn filter_entries<I>(entries: I, name_pattern: &str) -> Result<Vec<DirEntry>>
+where
+    I: Iterator<Item = Result<DirEntry>>,
+{
+    let name_pattern = Glob::new(name_pattern);
+    entries
+        .filter(|entry| entry.as_ref().map_or(true, |e| name_pattern.is_match(&e.name)))
+        .collect()
+}
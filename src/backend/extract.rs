@@ -0,0 +1,321 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::backend::{check_name, components, DirEntry, FileManager, Glob, WalkEvent};
+use crate::error::{Error, Result};
+use crate::pk2::FileTime;
+
+/// What [`Extract`] does with files that already exist on the host.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Existing files are replaced.
+    #[default]
+    Always,
+    /// Existing files are kept and reported as skipped.
+    Never,
+    /// Existing files are only replaced if the entry was modified after them.
+    IfNewer,
+    /// An existing file aborts the extraction with [`Error::AlreadyExists`]. All files are
+    /// checked before anything is written.
+    Error,
+}
+
+/// What happened to a file during an extraction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExtractAction {
+    /// The file did not exist on the host and was written.
+    Created,
+    /// An existing file was replaced.
+    Overwritten,
+    /// An existing file was kept because of the [`Overwrite`] policy.
+    Skipped,
+}
+
+/// Passed to the progress callback of [`Extract`] after every file.
+#[derive(Debug)]
+pub struct ExtractProgress<'a> {
+    pub entry: &'a DirEntry,
+    /// Path of the file on the host.
+    pub target: &'a Path,
+    pub action: ExtractAction,
+    /// Number of files handled so far, including this one.
+    pub files: usize,
+    pub total_files: usize,
+    /// Size of the files handled so far, skipped ones included.
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// The outcome of [`Extract::run`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtractSummary {
+    /// Archive paths of the files that were written.
+    pub written: Vec<String>,
+    /// Archive paths of the files that were kept because they already existed.
+    pub skipped: Vec<String>,
+    /// Number of directories created on the host.
+    pub directories: usize,
+    /// Number of bytes written.
+    pub bytes: u64,
+}
+
+/// The directories to create and files to extract, with their host paths relative to the target.
+struct Plan {
+    dirs: Vec<PathBuf>,
+    files: Vec<(DirEntry, PathBuf)>,
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&ExtractProgress<'_>) + 'a>;
+
+/// Copies a directory tree from an archive to the host filesystem, created with
+/// [`FileManager::extract`].
+///
+/// The children of the root end up directly in the target directory, missing directories are
+/// created. The timestamps of the entries are applied to the written files unless disabled with
+/// [`Extract::preserve_times`], zero timestamps are treated as unset. Names that are not a single
+/// plain name on the host, like `C:x` which windows reads as a drive prefix, are rejected with
+/// [`Error::InvalidPath`] before anything is written.
+///
+/// ```no_run
+/// use gfxfilemanager::backend::Overwrite;
+/// use gfxfilemanager::{FileManager, Pk2};
+///
+/// let archive = Pk2::open("Media.pk2", "169841")?;
+/// let summary = archive
+///     .extract("Media/textdata", "textdata")
+///     .include("**/*.txt")
+///     .exclude("old")
+///     .overwrite(Overwrite::IfNewer)
+///     .progress(|p| eprintln!("{}/{} {}", p.files, p.total_files, p.entry.path))
+///     .run()?;
+/// println!("wrote {} files, skipped {}", summary.written.len(), summary.skipped.len());
+/// # Ok::<(), gfxfilemanager::Error>(())
+/// ```
+pub struct Extract<'a, F: FileManager + 'a> {
+    file_manager: &'a F,
+    root: String,
+    target: PathBuf,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    overwrite: Overwrite,
+    preserve_times: bool,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a, F: FileManager> Extract<'a, F> {
+    pub(crate) fn new(file_manager: &'a F, root: &str, target: &Path) -> Self {
+        Extract {
+            file_manager,
+            root: root.to_owned(),
+            target: target.to_owned(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            overwrite: Overwrite::default(),
+            preserve_times: true,
+            progress: None,
+        }
+    }
+
+    /// Only extracts files whose path relative to the root matches one of the patterns added
+    /// with this method. Without include patterns every file is extracted, and directories are
+    /// created even if they are empty.
+    pub fn include(mut self, pattern: impl Into<Glob>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Skips files and whole directories whose path relative to the root matches one of the
+    /// patterns added with this method, even if they are included.
+    pub fn exclude(mut self, pattern: impl Into<Glob>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: Overwrite) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Whether the modification and access times, and the creation time on Windows, of the
+    /// entries are applied to the written files. Enabled by default.
+    pub fn preserve_times(mut self, preserve: bool) -> Self {
+        self.preserve_times = preserve;
+        self
+    }
+
+    /// Calls the closure after every file that was written or skipped.
+    pub fn progress<P>(mut self, progress: P) -> Self
+    where
+        P: FnMut(&ExtractProgress<'_>) + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Walks the archive and copies the selected files. Errors abort the extraction, the files
+    /// written until then are kept. Invalid names and, with [`Overwrite::Error`], existing files
+    /// are found before anything is written.
+    pub fn run(mut self) -> Result<ExtractSummary> {
+        let Plan { dirs, files } = self.plan()?;
+        let mut summary = ExtractSummary::default();
+        summary.directories += create_dir(&self.target)?;
+        for dir in &dirs {
+            summary.directories += create_dir(&self.target.join(dir))?;
+        }
+
+        let total_files = files.len();
+        let total_bytes = files.iter().map(|(file, _)| file.size).sum();
+        let mut bytes = 0;
+        for (idx, (file, relative)) in files.iter().enumerate() {
+            let target = self.target.join(relative);
+            let action = match fs::metadata(&target) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => ExtractAction::Created,
+                Err(e) => return Err(e.into()),
+                Ok(metadata) => match self.overwrite {
+                    Overwrite::Always => ExtractAction::Overwritten,
+                    Overwrite::Never => ExtractAction::Skipped,
+                    Overwrite::IfNewer => match metadata.modified() {
+                        Ok(modified) if FileTime::from(modified) >= file.modify_time => {
+                            ExtractAction::Skipped
+                        }
+                        _ => ExtractAction::Overwritten,
+                    },
+                    // only reached if the file was created while extracting
+                    Overwrite::Error => {
+                        return Err(Error::AlreadyExists(target.display().to_string()))
+                    }
+                },
+            };
+            if action == ExtractAction::Skipped {
+                summary.skipped.push(file.path.clone());
+            } else {
+                if let Some(parent) = target.parent() {
+                    summary.directories += create_dir(parent)?;
+                }
+                summary.bytes += self.write_file(file, &target)?;
+                summary.written.push(file.path.clone());
+            }
+            bytes += file.size;
+            if let Some(progress) = &mut self.progress {
+                progress(&ExtractProgress {
+                    entry: file,
+                    target: &target,
+                    action,
+                    files: idx + 1,
+                    total_files,
+                    bytes,
+                    total_bytes,
+                });
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Walks the archive to collect the directories and files to extract, and checks for
+    /// existing files if they are not to be overwritten.
+    fn plan(&self) -> Result<Plan> {
+        let root_len = self.root.len();
+        let relative = |entry: &DirEntry| {
+            entry
+                .path
+                .get(root_len..)
+                .unwrap_or(&entry.path)
+                .trim_start_matches(['/', '\\'])
+                .to_owned()
+        };
+        let excluded = |path: &str| self.exclude.iter().any(|pattern| pattern.is_match(path));
+
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        let walk = self.file_manager.walk(&self.root).prune(|dir| excluded(&relative(dir)));
+        for event in walk {
+            match event? {
+                WalkEvent::EnterDir(dir) if self.include.is_empty() => {
+                    dirs.push(host_path(&relative(&dir))?);
+                }
+                WalkEvent::File(file) => {
+                    let path = relative(&file);
+                    let included = self.include.is_empty()
+                        || self.include.iter().any(|pattern| pattern.is_match(&path));
+                    if included && !excluded(&path) {
+                        files.push((file, host_path(&path)?));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.overwrite == Overwrite::Error {
+            for (_, relative) in &files {
+                let target = self.target.join(relative);
+                match fs::metadata(&target) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                    Ok(_) => return Err(Error::AlreadyExists(target.display().to_string())),
+                }
+            }
+        }
+        Ok(Plan { dirs, files })
+    }
+
+    /// Copies the contents of the file and returns the number of bytes written.
+    fn write_file(&self, file: &DirEntry, target: &Path) -> Result<u64> {
+        let mut src = self.file_manager.open_file(&file.path)?;
+        let mut dst = fs::File::create(target)?;
+        let written = io::copy(&mut src, &mut dst)?;
+        if self.preserve_times {
+            dst.set_times(file_times(file))?;
+        }
+        Ok(written)
+    }
+}
+
+/// Turns a relative archive path into a relative host path, making sure that every component is
+/// a single plain name on the host. Names containing `:` are rejected everywhere, since windows
+/// turns `C:` and `C:x` into drive prefixes that escape the target directory.
+fn host_path(relative: &str) -> Result<PathBuf> {
+    components(relative)
+        .map(|name| {
+            check_name(name)?;
+            let mut host = Path::new(name).components();
+            match (host.next(), host.next()) {
+                (Some(Component::Normal(_)), None) if !name.contains(':') => Ok(name),
+                _ => Err(Error::InvalidPath(name.to_owned())),
+            }
+        })
+        .collect()
+}
+
+/// Creates the directory and its missing parents, returns the number of directories created.
+fn create_dir(path: &Path) -> Result<usize> {
+    if path.as_os_str().is_empty() || path.is_dir() {
+        return Ok(0);
+    }
+    let created = match path.parent() {
+        Some(parent) => create_dir(parent)?,
+        None => 0,
+    };
+    match fs::create_dir(path) {
+        Ok(()) => Ok(created + 1),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(created),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Collects the times of the entry that are set and can be represented on the host.
+fn file_times(entry: &DirEntry) -> fs::FileTimes {
+    let time = |time: FileTime| Some(time).filter(|&t| t != FileTime(0))?.to_system_time();
+    let mut times = fs::FileTimes::new();
+    if let Some(modified) = time(entry.modify_time) {
+        times = times.set_modified(modified);
+    }
+    if let Some(accessed) = time(entry.access_time) {
+        times = times.set_accessed(accessed);
+    }
+    #[cfg(windows)]
+    if let Some(created) = time(entry.create_time) {
+        use std::os::windows::fs::FileTimesExt;
+        times = times.set_created(created);
+    }
+    times
+}
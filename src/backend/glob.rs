@@ -0,0 +1,223 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::backend::components;
+
+/// A compiled glob pattern for archive paths.
+///
+/// The pattern is split into components at `/` and `\` like any other archive path, leading,
+/// trailing and repeated separators are ignored. Each component of a path is matched against the
+/// component of the pattern at the same position:
+///
+/// - `?` matches exactly one character.
+/// - `*` matches any number of characters, including none, but never a separator.
+/// - `**` as a whole component matches any number of path components, including none.
+///   Anywhere else it behaves like `*`.
+/// - Every other character matches itself, ignoring ASCII case like all lookups in the archive.
+///
+/// Dots have no special meaning, so `*.txt` matches `a.b.txt`, while `*.b` does not. The only
+/// exception is a pattern component ending in `.*`, which also matches names without that dot,
+/// so `*.*` matches every name and `item.*` matches `item` the same way the search of the dll
+/// does.
+///
+/// ```
+/// use gfxfilemanager::Glob;
+///
+/// let glob = Glob::new("media/**/*.TXT");
+/// assert!(glob.is_match("Media/textdata/item.txt"));
+/// assert!(glob.is_match("media\\item.txt"));
+/// assert!(!glob.is_match("media/textdata/item.txt.bak"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    /// `**`, matches any number of components.
+    Recursive,
+    /// Matches exactly one component.
+    Component(Vec<Token>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let segments = components(pattern)
+            .map(|component| match component {
+                "**" => Segment::Recursive,
+                _ => Segment::Component(tokenize(component)),
+            })
+            .collect();
+        Glob { pattern: pattern.to_owned(), segments }
+    }
+
+    /// Returns the pattern this glob was created from.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns true if the pattern contains no wildcards, so it only matches a single path.
+    pub fn is_literal(&self) -> bool {
+        self.segments.iter().all(|segment| match segment {
+            Segment::Recursive => false,
+            Segment::Component(tokens) => tokens.iter().all(|t| matches!(t, Token::Char(_))),
+        })
+    }
+
+    /// Returns the leading components of the pattern that contain no wildcards, joined with `/`
+    /// and starting with one if the pattern is absolute. Every matching path starts with this
+    /// directory, so it is where a search has to begin.
+    pub fn literal_prefix(&self) -> String {
+        let absolute = self.pattern.starts_with(['/', '\\']);
+        let mut prefix = String::from(if absolute { "/" } else { "" });
+        for (component, segment) in components(&self.pattern).zip(&self.segments) {
+            match segment {
+                Segment::Component(tokens)
+                    if tokens.iter().all(|t| matches!(t, Token::Char(_))) =>
+                {
+                    if !prefix.is_empty() && prefix != "/" {
+                        prefix.push('/');
+                    }
+                    prefix.push_str(component);
+                }
+                _ => break,
+            }
+        }
+        prefix
+    }
+
+    /// Returns true if the whole path matches the pattern.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.run(path).contains(&self.segments.len())
+    }
+
+    /// Returns true if a path inside the directory could match the pattern, meaning a search
+    /// has to descend into it. Directories for which this returns false can be skipped.
+    pub fn could_match_below(&self, dir: &str) -> bool {
+        self.run(dir).iter().any(|&state| state < self.segments.len())
+    }
+
+    /// Feeds the components of the path through the pattern and returns the indices of the
+    /// segments that could match next, an index equal to the number of segments means the
+    /// pattern has been matched completely. Tracking every possible state at once keeps the
+    /// matching linear in the length of the path, no matter how many `**` the pattern contains.
+    fn run(&self, path: &str) -> Vec<usize> {
+        let mut states = Vec::new();
+        self.add_state(&mut states, 0);
+        for component in components(path) {
+            let mut next = Vec::new();
+            for &state in &states {
+                match self.segments.get(state) {
+                    Some(Segment::Recursive) => self.add_state(&mut next, state),
+                    Some(Segment::Component(tokens)) if match_component(tokens, component) => {
+                        self.add_state(&mut next, state + 1)
+                    }
+                    _ => {}
+                }
+            }
+            if next.is_empty() {
+                return next;
+            }
+            states = next;
+        }
+        states
+    }
+
+    /// Adds a state and every state reachable from it by letting `**` match nothing.
+    fn add_state(&self, states: &mut Vec<usize>, mut state: usize) {
+        loop {
+            if !states.contains(&state) {
+                states.push(state);
+            }
+            match self.segments.get(state) {
+                Some(Segment::Recursive) => state += 1,
+                _ => break,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl FromStr for Glob {
+    type Err = std::convert::Infallible;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Ok(Glob::new(pattern))
+    }
+}
+
+impl From<&str> for Glob {
+    fn from(pattern: &str) -> Self {
+        Glob::new(pattern)
+    }
+}
+
+fn tokenize(component: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for c in component.chars() {
+        let token = match c {
+            '?' => Token::Any,
+            // consecutive stars match the same as a single one
+            '*' if tokens.last() == Some(&Token::Star) => continue,
+            '*' => Token::Star,
+            _ => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Matches a single name against the tokens of a pattern component.
+fn match_component(tokens: &[Token], name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let (mut t, mut n) = (0, 0);
+    // position of the last `*` in the pattern and the name position it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                backtrack = Some((t, n));
+                t += 1;
+            }
+            Some(Token::Any) => {
+                t += 1;
+                n += 1;
+            }
+            Some(Token::Char(c)) if c.eq_ignore_ascii_case(&name[n]) => {
+                t += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    t = star + 1;
+                    n = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    let rest = &tokens[t..];
+    // a trailing `.*` may also match the end of a name without a dot
+    let rest = match rest {
+        [Token::Char('.'), Token::Star] => &rest[2..],
+        _ => rest,
+    };
+    rest.iter().all(|&token| token == Token::Star)
+}
@@ -0,0 +1,253 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::{check_name, join, resolve, ArchivePath, DirEntry, FileManager};
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, FileTime};
+
+/// A native counterpart of the dll's `Mode::CW`, which maps the operations onto a
+/// directory of the host filesystem instead of a container, for example an extracted client.
+///
+/// The semantics follow the pk2 implementation, so lookups are case-insensitive even on case
+/// sensitive filesystems and names are limited to 80 bytes. Newly created entries keep the case
+/// they were created with.
+///
+/// Symbolic links are followed as long as they lead to a place inside the root directory, links
+/// that lead outside of it are rejected with [`Error::InvalidPath`].
+pub struct HostFileManager {
+    root: PathBuf,
+    /// The root with all symbolic links resolved, which every link has to lead into.
+    canonical_root: PathBuf,
+    cwd: ArchivePath,
+}
+
+impl HostFileManager {
+    /// Creates a file manager rooted at the given, existing directory.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<Self> {
+        let mut fm = HostFileManager {
+            root: PathBuf::new(),
+            canonical_root: PathBuf::new(),
+            cwd: ArchivePath::root(),
+        };
+        fm.set_virtual_path(root)?;
+        Ok(fm)
+    }
+
+    /// Returns the host directory the archive root is mapped to.
+    pub fn virtual_path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Maps the archive root to another host directory and resets the current directory.
+    pub fn set_virtual_path<P: Into<PathBuf>>(&mut self, root: P) -> Result<()> {
+        let root = root.into();
+        if !fs::metadata(&root)?.is_dir() {
+            return Err(Error::NotADirectory(root.display().to_string()));
+        }
+        self.canonical_root = fs::canonicalize(&root)?;
+        self.root = root;
+        self.cwd = ArchivePath::root();
+        Ok(())
+    }
+
+    /// Returns the host path of an existing entry.
+    fn host_path(&self, path: &str) -> Result<PathBuf> {
+        self.host_path_components(&resolve(&self.cwd, path)?)
+    }
+
+    fn host_path_components(&self, components: &[String]) -> Result<PathBuf> {
+        let mut host_path = self.root.clone();
+        for (depth, name) in components.iter().enumerate() {
+            host_path = self
+                .find_child(&host_path, name)?
+                .ok_or_else(|| Error::NotFound(components[..=depth].join("/")))?;
+        }
+        Ok(host_path)
+    }
+
+    /// Returns the host path of the directory at the given components, creating the missing
+    /// ones.
+    fn ensure_directory(&self, components: &[String]) -> Result<PathBuf> {
+        let mut host_path = self.root.clone();
+        for (depth, name) in components.iter().enumerate() {
+            host_path = match self.find_child(&host_path, name)? {
+                Some(child) if child.is_dir() => child,
+                Some(_) => return Err(Error::AlreadyExists(components[..=depth].join("/"))),
+                None => {
+                    check_name(name)?;
+                    let child = host_path.join(name);
+                    fs::create_dir(&child)?;
+                    child
+                }
+            };
+        }
+        Ok(host_path)
+    }
+
+    /// Finds the child of `dir` called `name`, ignoring ASCII case if there is no exact match.
+    /// A symbolic link is only returned if it leads to a place inside the root.
+    fn find_child(&self, dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+        let Some(child) = lookup_child(dir, name)? else {
+            return Ok(None);
+        };
+        if fs::symlink_metadata(&child)?.file_type().is_symlink() {
+            // a dangling link could still be created through, so it is rejected as well
+            match fs::canonicalize(&child) {
+                Ok(target) if target.starts_with(&self.canonical_root) => {}
+                _ => return Err(Error::InvalidPath(name.to_owned())),
+            }
+        }
+        Ok(Some(child))
+    }
+}
+
+impl FileManager for HostFileManager {
+    type File<'a> = fs::File;
+    type FileMut<'a> = fs::File;
+    type ReadDir<'a> = HostReadDir;
+
+    fn open_file(&self, path: &str) -> Result<fs::File> {
+        let host_path = self.host_path(path)?;
+        if !host_path.is_file() {
+            return Err(Error::NotAFile(path.to_owned()));
+        }
+        Ok(fs::File::open(host_path)?)
+    }
+
+    fn create_file(&self, path: &str) -> Result<fs::File> {
+        let mut components = resolve(&self.cwd, path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        let dir = self.ensure_directory(&components)?;
+        let host_path = match self.find_child(&dir, &name)? {
+            Some(child) if child.is_dir() => return Err(Error::AlreadyExists(path.to_owned())),
+            Some(child) => child,
+            None => {
+                check_name(&name)?;
+                dir.join(name)
+            }
+        };
+        Ok(fs::File::create(host_path)?)
+    }
+
+    fn delete_file(&self, path: &str) -> Result<()> {
+        let host_path = self.host_path(path)?;
+        if !host_path.is_file() {
+            return Err(Error::NotAFile(path.to_owned()));
+        }
+        Ok(fs::remove_file(host_path)?)
+    }
+
+    fn create_directory(&self, path: &str) -> Result<()> {
+        self.ensure_directory(&resolve(&self.cwd, path)?).map(drop)
+    }
+
+    fn delete_directory(&self, path: &str) -> Result<()> {
+        let components = resolve(&self.cwd, path)?;
+        if components.is_empty() {
+            return Err(Error::InvalidPath(path.to_owned()));
+        }
+        let host_path = self.host_path_components(&components)?;
+        if !host_path.is_dir() {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        if fs::read_dir(&host_path)?.next().is_some() {
+            return Err(Error::DirectoryNotEmpty(path.to_owned()));
+        }
+        Ok(fs::remove_dir(host_path)?)
+    }
+
+    fn change_directory(&mut self, path: &str) -> Result<()> {
+        let cwd = self.cwd.resolve(path)?;
+        if !self.host_path_components(cwd.components())?.is_dir() {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    fn reset_directory(&mut self) -> Result<()> {
+        self.cwd = ArchivePath::root();
+        Ok(())
+    }
+
+    fn current_directory(&self) -> Result<String> {
+        Ok(self.cwd.to_string())
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool> {
+        match self.host_path(path) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_dir(&self, path: &str) -> Result<HostReadDir> {
+        let dir = self.host_path(path)?;
+        if !dir.is_dir() {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        Ok(HostReadDir { inner: fs::read_dir(dir)?, path: path.to_owned() })
+    }
+}
+
+/// Iterator over the entries of a host directory, returned by [`FileManager::read_dir`].
+/// Entries whose names are not valid unicode are skipped since no archive path can address them.
+pub struct HostReadDir {
+    inner: fs::ReadDir,
+    path: String,
+}
+
+impl Iterator for HostReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        loop {
+            let entry = match self.inner.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            return Some(self.dir_entry(&entry, name));
+        }
+    }
+}
+
+impl HostReadDir {
+    fn dir_entry(&self, entry: &fs::DirEntry, name: String) -> Result<DirEntry> {
+        let metadata = entry.metadata()?;
+        let modify_time = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        Ok(DirEntry {
+            path: join(&self.path, &name),
+            name,
+            kind: if metadata.is_dir() { EntryKind::Directory } else { EntryKind::File },
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            create_time: FileTime::from(metadata.created().unwrap_or(modify_time)),
+            modify_time: FileTime::from(modify_time),
+            access_time: FileTime::from(metadata.accessed().unwrap_or(modify_time)),
+            position: None,
+        })
+    }
+}
+
+/// Finds the child of `dir` called `name` like [`HostFileManager::find_child`], without checking
+/// where symbolic links lead.
+fn lookup_child(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    let exact = dir.join(name);
+    if fs::symlink_metadata(&exact).is_ok() {
+        return Ok(Some(exact));
+    }
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_str().is_some_and(|n| n.eq_ignore_ascii_case(name)) {
+            return Ok(Some(entry.path()));
+        }
+    }
+    Ok(None)
+}
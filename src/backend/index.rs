@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops;
+use std::path::Path;
+
+use crate::backend::{ArchivePath, DirEntry, FileManager, WalkEvent};
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, FileTime};
+
+const MAGIC: &[u8; 8] = b"PK2INDEX";
+const FORMAT_VERSION: u32 = 1;
+
+/// Identifies an entry of an [`Index`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// An entry of an [`Index`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Size of the file in bytes, zero for directories.
+    pub size: u64,
+    pub create_time: FileTime,
+    pub modify_time: FileTime,
+    pub access_time: FileTime,
+    pub position: Option<u64>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    total_size: u64,
+}
+
+impl IndexEntry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    /// Returns the directory containing the entry, `None` for the root.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// Returns the entries of a directory in the order they are stored in.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Returns the size of a file, or the sum of the sizes of all files below a directory.
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+}
+
+/// Identifies the state of a container file an index was built from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ContainerStamp {
+    pub len: u64,
+    pub modified: FileTime,
+}
+
+impl ContainerStamp {
+    /// Reads the size and modification time of the file at the given path.
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(ContainerStamp { len: metadata.len(), modified: FileTime::from(metadata.modified()?) })
+    }
+}
+
+/// An in-memory tree of every entry in an archive, built in a single pass over all directories.
+///
+/// Looking up a path takes constant time no matter how many entries the archive holds, and every
+/// directory knows the total size of the files below it. Since building the index of a large
+/// container takes a while, it can be written to disk and reused with [`Index::cached`] as long
+/// as the container did not change.
+///
+/// The index is a snapshot, it does not see changes made to the archive after it was built.
+///
+/// ```
+/// use gfxfilemanager::{FileManager, Index, MemoryArchive};
+///
+/// let archive = MemoryArchive::from_files([
+///     ("media/textdata/item.txt", "item"),
+///     ("media/icon/item.ddj", "icon data"),
+/// ])?;
+/// let index = Index::build(&archive)?;
+/// let media = index.lookup("MEDIA").unwrap();
+/// assert_eq!(index[media].total_size(), 13);
+/// assert_eq!(index[media].children().len(), 2);
+/// let item = index.lookup("media\\textdata\\item.txt").unwrap();
+/// assert_eq!(index.path(item), "media/textdata/item.txt");
+/// # Ok::<(), gfxfilemanager::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Index {
+    /// All entries, parents always come before their children. The first one is the root.
+    entries: Vec<IndexEntry>,
+    /// Maps lowercase paths to their entries.
+    paths: HashMap<String, NodeId>,
+    stamp: Option<ContainerStamp>,
+}
+
+impl Index {
+    /// Builds the index of the whole archive.
+    pub fn build<F: FileManager>(file_manager: &F) -> Result<Self> {
+        let mut index = Index::empty();
+        let mut dirs = vec![index.root()];
+        for event in file_manager.walk("/") {
+            match event? {
+                WalkEvent::EnterDir(entry) => {
+                    let parent = *dirs.last().unwrap();
+                    dirs.push(index.push(parent, entry));
+                }
+                WalkEvent::LeaveDir(_) => {
+                    dirs.pop();
+                }
+                WalkEvent::File(entry) => {
+                    let parent = *dirs.last().unwrap();
+                    index.push(parent, entry);
+                }
+            }
+        }
+        index.finish();
+        Ok(index)
+    }
+
+    /// Loads the index of the container at `container_path` from `cache_path`, if the cache was
+    /// written for the container in its current state. Otherwise the index is built from the
+    /// archive returned by `open` and written to the cache for the next time.
+    pub fn cached<F, O>(cache_path: &Path, container_path: &Path, open: O) -> Result<Self>
+    where
+        F: FileManager,
+        O: FnOnce() -> Result<F>,
+    {
+        let stamp = ContainerStamp::of(container_path)?;
+        if let Ok(index) = Index::load(cache_path) {
+            if index.stamp == Some(stamp) {
+                return Ok(index);
+            }
+        }
+        let mut index = Index::build(&open()?)?;
+        index.stamp = Some(stamp);
+        index.save(cache_path)?;
+        Ok(index)
+    }
+
+    fn empty() -> Self {
+        let root = IndexEntry {
+            name: String::new(),
+            kind: EntryKind::Directory,
+            size: 0,
+            create_time: FileTime::default(),
+            modify_time: FileTime::default(),
+            access_time: FileTime::default(),
+            position: None,
+            parent: None,
+            children: Vec::new(),
+            total_size: 0,
+        };
+        Index { entries: vec![root], paths: HashMap::new(), stamp: None }
+    }
+
+    fn push(&mut self, parent: NodeId, entry: DirEntry) -> NodeId {
+        let id = NodeId(self.entries.len());
+        self.entries[parent.0].children.push(id);
+        self.entries.push(IndexEntry {
+            name: entry.name,
+            kind: entry.kind,
+            size: entry.size,
+            create_time: entry.create_time,
+            modify_time: entry.modify_time,
+            access_time: entry.access_time,
+            position: entry.position,
+            parent: Some(parent),
+            children: Vec::new(),
+            total_size: 0,
+        });
+        id
+    }
+
+    /// Fills in the lookup table and the total sizes once all entries have been added.
+    fn finish(&mut self) {
+        for idx in (1..self.entries.len()).rev() {
+            let entry = &mut self.entries[idx];
+            if entry.is_file() {
+                entry.total_size = entry.size;
+            }
+            let (total_size, parent) = (entry.total_size, entry.parent);
+            if let Some(parent) = parent {
+                self.entries[parent.0].total_size += total_size;
+            }
+        }
+        self.paths = (1..self.entries.len())
+            .map(|idx| (self.path(NodeId(idx)).to_ascii_lowercase(), NodeId(idx)))
+            .collect();
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Returns the number of entries, including the root.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the archive contains no entries besides its root.
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 1
+    }
+
+    /// Returns the size and modification time of the container the index was built from, if it
+    /// was loaded or built by [`Index::cached`].
+    pub fn stamp(&self) -> Option<ContainerStamp> {
+        self.stamp
+    }
+
+    pub fn set_stamp(&mut self, stamp: Option<ContainerStamp>) {
+        self.stamp = stamp;
+    }
+
+    /// Looks up the entry at the given path, relative to the root of the archive.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        let path = ArchivePath::new(path).ok()?;
+        if path.is_root() {
+            return Some(self.root());
+        }
+        self.paths.get(&path.to_string().to_ascii_lowercase()).copied()
+    }
+
+    /// Looks up the entry at the given path, see [`Index::lookup`].
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.lookup(path).map(|id| &self[id])
+    }
+
+    /// Returns the full path of the entry, joined with `/`.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current.filter(|&id| id != self.root()) {
+            names.push(self[id].name.as_str());
+            current = self[id].parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Converts the entry into the representation returned by [`FileManager::read_dir`].
+    pub fn dir_entry(&self, id: NodeId) -> DirEntry {
+        let entry = &self[id];
+        DirEntry {
+            path: self.path(id),
+            name: entry.name.clone(),
+            kind: entry.kind,
+            size: entry.size,
+            create_time: entry.create_time,
+            modify_time: entry.modify_time,
+            access_time: entry.access_time,
+            position: entry.position,
+        }
+    }
+
+    /// Returns an iterator over all entries besides the root, parents come before their
+    /// children.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &IndexEntry)> + '_ {
+        self.entries.iter().enumerate().skip(1).map(|(idx, entry)| (NodeId(idx), entry))
+    }
+
+    /// Reads an index written by [`Index::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Index::read_from(BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Writes the index to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Serializes the index, all numbers are stored in little endian.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        let (len, modified) = self.stamp.map_or((0, 0), |stamp| (stamp.len, stamp.modified.0));
+        writer.write_all(&[self.stamp.is_some() as u8])?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&modified.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64 - 1).to_le_bytes())?;
+        for (_, entry) in self.iter() {
+            let parent = entry.parent.map_or(0, |parent| parent.0) as u64;
+            writer.write_all(&parent.to_le_bytes())?;
+            writer.write_all(&[entry.kind as u8])?;
+            writer.write_all(&(entry.name.len() as u16).to_le_bytes())?;
+            writer.write_all(entry.name.as_bytes())?;
+            for value in [
+                entry.size,
+                entry.position.unwrap_or(u64::MAX),
+                entry.create_time.0,
+                entry.modify_time.0,
+                entry.access_time.0,
+            ] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes an index written by [`Index::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::InvalidIndex("missing signature"));
+        }
+        if read_u32(&mut reader)? != FORMAT_VERSION {
+            return Err(Error::InvalidIndex("unsupported version"));
+        }
+        let mut has_stamp = [0];
+        read_exact(&mut reader, &mut has_stamp)?;
+        let stamp = ContainerStamp {
+            len: read_u64(&mut reader)?,
+            modified: FileTime(read_u64(&mut reader)?),
+        };
+
+        let mut index = Index::empty();
+        index.stamp = (has_stamp[0] != 0).then_some(stamp);
+        let count = read_u64(&mut reader)?;
+        for idx in 1..=count {
+            let parent = read_u64(&mut reader)?;
+            if parent >= idx || !index.entries[parent as usize].is_dir() {
+                return Err(Error::InvalidIndex("invalid parent"));
+            }
+            let mut kind_and_len = [0; 3];
+            read_exact(&mut reader, &mut kind_and_len)?;
+            let kind = match kind_and_len[0] {
+                1 => EntryKind::Directory,
+                2 => EntryKind::File,
+                _ => return Err(Error::InvalidIndex("invalid entry type")),
+            };
+            let mut name = vec![0; u16::from_le_bytes([kind_and_len[1], kind_and_len[2]]) as usize];
+            read_exact(&mut reader, &mut name)?;
+            let name = String::from_utf8(name).map_err(|_| Error::InvalidIndex("invalid name"))?;
+            let size = read_u64(&mut reader)?;
+            let position = Some(read_u64(&mut reader)?).filter(|&pos| pos != u64::MAX);
+            let entry = DirEntry {
+                path: String::new(),
+                name,
+                kind,
+                size,
+                create_time: FileTime(read_u64(&mut reader)?),
+                modify_time: FileTime(read_u64(&mut reader)?),
+                access_time: FileTime(read_u64(&mut reader)?),
+                position,
+            };
+            index.push(NodeId(parent as usize), entry);
+        }
+        index.finish();
+        Ok(index)
+    }
+}
+
+impl ops::Index<NodeId> for Index {
+    type Output = IndexEntry;
+
+    fn index(&self, id: NodeId) -> &IndexEntry {
+        &self.entries[id.0]
+    }
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidIndex("unexpected end of file"),
+        _ => Error::Io(e),
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0; 8];
+    read_exact(reader, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
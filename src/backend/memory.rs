@@ -0,0 +1,326 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+
+use std::vec;
+
+use crate::backend::{check_name, join, resolve, ArchivePath, DirEntry, FileManager};
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, FileTime};
+
+/// An archive that only lives in memory, useful to test code written against [`FileManager`]
+/// without a real container.
+///
+/// It follows the semantics of the native pk2 implementation: lookups are case-insensitive, names
+/// are limited to 80 bytes and creating a file creates its missing parent directories.
+#[derive(Default)]
+pub struct MemoryArchive {
+    root: RefCell<Directory>,
+    cwd: ArchivePath,
+}
+
+#[derive(Default)]
+struct Directory {
+    /// Children keyed by their lowercased name.
+    children: BTreeMap<String, Node>,
+}
+
+struct Node {
+    name: String,
+    create_time: FileTime,
+    modify_time: FileTime,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Directory(Directory),
+    File(Vec<u8>),
+}
+
+impl Node {
+    fn new(name: &str, kind: NodeKind) -> Self {
+        let now = FileTime::now();
+        Node { name: name.to_owned(), create_time: now, modify_time: now, kind }
+    }
+
+    fn dir_entry(&self, dir: &str) -> DirEntry {
+        let (kind, size) = match &self.kind {
+            NodeKind::Directory(_) => (EntryKind::Directory, 0),
+            NodeKind::File(data) => (EntryKind::File, data.len() as u64),
+        };
+        DirEntry {
+            path: join(dir, &self.name),
+            name: self.name.clone(),
+            kind,
+            size,
+            create_time: self.create_time,
+            modify_time: self.modify_time,
+            access_time: self.modify_time,
+            position: None,
+        }
+    }
+}
+
+impl Directory {
+    fn get(&self, name: &str) -> Option<&Node> {
+        self.children.get(&name.to_ascii_lowercase())
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.children.get_mut(&name.to_ascii_lowercase())
+    }
+
+    /// Returns the directory at the given components.
+    fn dir(&self, components: &[String]) -> Result<&Directory> {
+        let mut dir = self;
+        for (depth, name) in components.iter().enumerate() {
+            dir = match dir.get(name) {
+                Some(Node { kind: NodeKind::Directory(dir), .. }) => dir,
+                Some(_) => return Err(Error::NotADirectory(components[..=depth].join("/"))),
+                None => return Err(Error::NotFound(components[..=depth].join("/"))),
+            };
+        }
+        Ok(dir)
+    }
+
+    /// Returns the directory at the given components, creating the missing ones if `create` is
+    /// set.
+    fn dir_mut(&mut self, components: &[String], create: bool) -> Result<&mut Directory> {
+        let mut dir = self;
+        for (depth, name) in components.iter().enumerate() {
+            if create && dir.get(name).is_none() {
+                check_name(name)?;
+                let node = Node::new(name, NodeKind::Directory(Directory::default()));
+                dir.children.insert(name.to_ascii_lowercase(), node);
+            }
+            let path = || components[..=depth].join("/");
+            dir = match dir.get_mut(name) {
+                Some(Node { kind: NodeKind::Directory(dir), .. }) => dir,
+                Some(_) if create => return Err(Error::AlreadyExists(path())),
+                Some(_) => return Err(Error::NotADirectory(path())),
+                None => return Err(Error::NotFound(path())),
+            };
+        }
+        Ok(dir)
+    }
+
+    fn collect_files(&self, prefix: &str, files: &mut BTreeMap<String, Vec<u8>>) {
+        for node in self.children.values() {
+            let path = format!("{prefix}{}", node.name);
+            match &node.kind {
+                NodeKind::Directory(dir) => dir.collect_files(&format!("{path}/"), files),
+                NodeKind::File(data) => {
+                    files.insert(path, data.clone());
+                }
+            }
+        }
+    }
+}
+
+impl MemoryArchive {
+    pub fn new() -> Self {
+        MemoryArchive::default()
+    }
+
+    /// Creates an archive containing the given files, keyed by their path.
+    pub fn from_files<I, P, D>(files: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (P, D)>,
+        P: AsRef<str>,
+        D: Into<Vec<u8>>,
+    {
+        let archive = MemoryArchive::new();
+        for (path, data) in files {
+            archive.write(path.as_ref(), data.into())?;
+        }
+        Ok(archive)
+    }
+
+    /// Returns the contents of all files keyed by their absolute path, using `/` as separator.
+    pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        self.root.borrow().collect_files("", &mut files);
+        files
+    }
+
+    /// Returns the contents of the file at the given path.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let mut components = resolve(&self.cwd, path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        match self.root.borrow().dir(&components)?.get(&name) {
+            Some(Node { kind: NodeKind::File(data), .. }) => Ok(data.clone()),
+            Some(_) => Err(Error::NotAFile(path.to_owned())),
+            None => Err(Error::NotFound(path.to_owned())),
+        }
+    }
+
+    /// Stores the file at the given path, replacing its contents if it already exists.
+    pub fn write(&self, path: &str, data: Vec<u8>) -> Result<()> {
+        let mut components = resolve(&self.cwd, path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        self.write_components(&components, &name, data)
+    }
+
+    fn write_components(&self, components: &[String], name: &str, data: Vec<u8>) -> Result<()> {
+        let mut root = self.root.borrow_mut();
+        let dir = root.dir_mut(components, true)?;
+        match dir.get_mut(name) {
+            Some(Node { kind: NodeKind::Directory(_), .. }) => {
+                Err(Error::AlreadyExists(join(&components.join("/"), name)))
+            }
+            Some(node) => {
+                node.kind = NodeKind::File(data);
+                node.modify_time = FileTime::now();
+                Ok(())
+            }
+            None => {
+                check_name(name)?;
+                dir.children
+                    .insert(name.to_ascii_lowercase(), Node::new(name, NodeKind::File(data)));
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the entry at the given path after `check` approved it.
+    fn remove(&self, path: &str, check: impl FnOnce(&Node) -> Result<()>) -> Result<()> {
+        let mut components = resolve(&self.cwd, path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        let mut root = self.root.borrow_mut();
+        let dir = root.dir_mut(&components, false)?;
+        let node = dir.get(&name).ok_or_else(|| Error::NotFound(path.to_owned()))?;
+        check(node)?;
+        dir.children.remove(&name.to_ascii_lowercase());
+        Ok(())
+    }
+}
+
+impl FileManager for MemoryArchive {
+    type File<'a> = Cursor<Vec<u8>>;
+    type FileMut<'a> = MemoryFileMut<'a>;
+    type ReadDir<'a> = vec::IntoIter<Result<DirEntry>>;
+
+    /// Opens a copy of the file's contents.
+    fn open_file(&self, path: &str) -> Result<Cursor<Vec<u8>>> {
+        self.read(path).map(Cursor::new)
+    }
+
+    fn create_file(&self, path: &str) -> Result<MemoryFileMut<'_>> {
+        let mut components = resolve(&self.cwd, path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        {
+            let mut root = self.root.borrow_mut();
+            let dir = root.dir_mut(&components, true)?;
+            match dir.get(&name) {
+                Some(Node { kind: NodeKind::Directory(_), .. }) => {
+                    return Err(Error::AlreadyExists(path.to_owned()))
+                }
+                Some(_) => {}
+                None => {
+                    check_name(&name)?;
+                    let node = Node::new(&name, NodeKind::File(Vec::new()));
+                    dir.children.insert(name.to_ascii_lowercase(), node);
+                }
+            }
+        }
+        Ok(MemoryFileMut {
+            archive: self,
+            dir: components,
+            name,
+            data: Cursor::new(Vec::new()),
+            dirty: true,
+        })
+    }
+
+    fn delete_file(&self, path: &str) -> Result<()> {
+        self.remove(path, |node| match node.kind {
+            NodeKind::File(_) => Ok(()),
+            NodeKind::Directory(_) => Err(Error::NotAFile(path.to_owned())),
+        })
+    }
+
+    fn create_directory(&self, path: &str) -> Result<()> {
+        let components = resolve(&self.cwd, path)?;
+        self.root.borrow_mut().dir_mut(&components, true).map(drop)
+    }
+
+    fn delete_directory(&self, path: &str) -> Result<()> {
+        self.remove(path, |node| match &node.kind {
+            NodeKind::Directory(dir) if dir.children.is_empty() => Ok(()),
+            NodeKind::Directory(_) => Err(Error::DirectoryNotEmpty(path.to_owned())),
+            NodeKind::File(_) => Err(Error::NotADirectory(path.to_owned())),
+        })
+    }
+
+    fn change_directory(&mut self, path: &str) -> Result<()> {
+        let cwd = self.cwd.resolve(path)?;
+        self.root.borrow().dir(cwd.components())?;
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    fn reset_directory(&mut self) -> Result<()> {
+        self.cwd = ArchivePath::root();
+        Ok(())
+    }
+
+    fn current_directory(&self) -> Result<String> {
+        Ok(self.cwd.to_string())
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool> {
+        let mut components = resolve(&self.cwd, path)?;
+        let Some(name) = components.pop() else {
+            return Ok(true);
+        };
+        let root = self.root.borrow();
+        Ok(root.dir(&components).is_ok_and(|dir| dir.get(&name).is_some()))
+    }
+
+    /// Returns a snapshot of the directory's entries, the access time is the last write time
+    /// since it is not tracked.
+    fn read_dir(&self, path: &str) -> Result<vec::IntoIter<Result<DirEntry>>> {
+        let root = self.root.borrow();
+        let dir = root.dir(&resolve(&self.cwd, path)?)?;
+        let entries: Vec<_> = dir.children.values().map(|node| Ok(node.dir_entry(path))).collect();
+        Ok(entries.into_iter())
+    }
+}
+
+/// A file of a [`MemoryArchive`] opened for writing, its contents are stored in the archive when
+/// it is flushed or dropped.
+pub struct MemoryFileMut<'a> {
+    archive: &'a MemoryArchive,
+    dir: Vec<String>,
+    name: String,
+    data: Cursor<Vec<u8>>,
+    dirty: bool,
+}
+
+impl Write for MemoryFileMut<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.dirty = true;
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.dirty {
+            let data = self.data.get_ref().clone();
+            self.archive.write_components(&self.dir, &self.name, data).map_err(io::Error::from)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFileMut<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.data.seek(pos)
+    }
+}
+
+impl Drop for MemoryFileMut<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
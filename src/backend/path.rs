@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::backend::components;
+use crate::error::{Error, Result};
+use crate::pk2::MAX_NAME_LEN;
+
+/// A normalized, absolute path inside an archive.
+///
+/// Paths are parsed like the dll does it: `/` and `\` are both separators, repeated and trailing
+/// separators are ignored, `.` refers to the directory itself and `..` to its parent. Every
+/// component has to fit into the name field of an entry, which holds at most
+/// [`MAX_NAME_LEN`] bytes followed by a null terminator, longer names are rejected with
+/// [`Error::NameTooLong`] instead of being truncated.
+///
+/// The original spelling of the components is kept for display, but comparisons and hashing
+/// ignore ASCII case, since lookups in the archive do as well.
+///
+/// ```
+/// use gfxfilemanager::ArchivePath;
+///
+/// let cwd = ArchivePath::new("Media\\textdata")?;
+/// let path = cwd.resolve("../icon/./item.ddj")?;
+/// assert_eq!(path.to_string(), "Media/icon/item.ddj");
+/// assert_eq!(path, ArchivePath::new("/media/ICON/item.ddj")?);
+/// assert_eq!(path.to_dll_path(), "\\Media\\icon\\item.ddj");
+/// # Ok::<(), gfxfilemanager::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ArchivePath {
+    components: Vec<String>,
+}
+
+impl ArchivePath {
+    /// Returns the root directory of the archive.
+    pub fn root() -> Self {
+        ArchivePath::default()
+    }
+
+    /// Parses a path relative to the root, a leading separator makes no difference.
+    pub fn new(path: &str) -> Result<Self> {
+        ArchivePath::root().resolve(path)
+    }
+
+    /// Resolves a path against this directory, unless it starts with a separator, which makes it
+    /// relative to the root. A `..` in the root stays in the root, so a path can never leave the
+    /// archive.
+    pub fn resolve(&self, path: &str) -> Result<Self> {
+        let mut resolved = if path.starts_with(['/', '\\']) { Self::root() } else { self.clone() };
+        for component in components(path) {
+            match component {
+                "." => {}
+                ".." => {
+                    resolved.components.pop();
+                }
+                _ => {
+                    check_name(component)?;
+                    resolved.components.push(component.to_owned());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Appends a path to this one, a leading separator is ignored instead of starting from the
+    /// root.
+    pub fn join(&self, path: &str) -> Result<Self> {
+        self.resolve(path.trim_start_matches(['/', '\\']))
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+
+    pub fn into_components(self) -> Vec<String> {
+        self.components
+    }
+
+    /// Returns the last component, `None` for the root.
+    pub fn name(&self) -> Option<&str> {
+        self.components.last().map(String::as_str)
+    }
+
+    /// Returns the directory containing this path, `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.components.split_last()?;
+        Some(ArchivePath { components: parent.to_vec() })
+    }
+
+    /// Returns true if `base` is this path or one of its ancestors, ignoring case.
+    pub fn starts_with(&self, base: &ArchivePath) -> bool {
+        base.components.len() <= self.components.len()
+            && base.components.iter().zip(&self.components).all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// Formats the path the way the dll expects it, absolute and with backslashes.
+    pub fn to_dll_path(&self) -> String {
+        format!("\\{}", self.components.join("\\"))
+    }
+}
+
+impl fmt::Display for ArchivePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.components.join("/"))
+    }
+}
+
+impl FromStr for ArchivePath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        ArchivePath::new(path)
+    }
+}
+
+impl TryFrom<&str> for ArchivePath {
+    type Error = Error;
+
+    fn try_from(path: &str) -> Result<Self> {
+        ArchivePath::new(path)
+    }
+}
+
+impl PartialEq for ArchivePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ArchivePath {}
+
+impl PartialOrd for ArchivePath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArchivePath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lowercase = |c: &String| c.bytes().map(|b| b.to_ascii_lowercase()).collect::<Vec<_>>();
+        self.components.iter().map(lowercase).cmp(other.components.iter().map(lowercase))
+    }
+}
+
+impl Hash for ArchivePath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for component in &self.components {
+            state.write_usize(component.len());
+            for b in component.bytes() {
+                state.write_u8(b.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+/// Checks that the name can be stored in an entry.
+pub(crate) fn check_name(name: &str) -> Result<()> {
+    check_stored_name(name.as_bytes(), name)
+}
+
+/// Like [`check_name`] for the bytes of a name as they are stored, which may be in another
+/// encoding than UTF-8. `name` is the decoded name reported in errors.
+pub(crate) fn check_stored_name(raw: &[u8], name: &str) -> Result<()> {
+    if raw.len() > MAX_NAME_LEN {
+        Err(Error::NameTooLong(name.to_owned()))
+    } else if raw.is_empty()
+        || raw == b"."
+        || raw == b".."
+        || raw.iter().any(|b| matches!(b, 0 | b'/' | b'\\'))
+    {
+        Err(Error::InvalidPath(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the names in a path that is passed on unresolved, for example to the dll, which would
+/// silently truncate names that are too long.
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
+pub(crate) fn check_names(path: &str) -> Result<()> {
+    components(path).filter(|&c| c != "." && c != "..").try_for_each(check_name)
+}
@@ -0,0 +1,185 @@
+use crate::backend::{DirEntry, FileManager, Glob};
+use crate::error::Result;
+
+/// An event reported by [`Walk`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalkEvent {
+    /// A directory is entered, the events of its children follow.
+    EnterDir(DirEntry),
+    /// All children of the directory have been reported.
+    LeaveDir(DirEntry),
+    File(DirEntry),
+}
+
+impl WalkEvent {
+    pub fn entry(&self) -> &DirEntry {
+        match self {
+            WalkEvent::EnterDir(entry) | WalkEvent::LeaveDir(entry) | WalkEvent::File(entry) => {
+                entry
+            }
+        }
+    }
+
+    pub fn into_entry(self) -> DirEntry {
+        match self {
+            WalkEvent::EnterDir(entry) | WalkEvent::LeaveDir(entry) | WalkEvent::File(entry) => {
+                entry
+            }
+        }
+    }
+}
+
+type PruneFn<'a> = Box<dyn FnMut(&DirEntry) -> bool + 'a>;
+
+/// A recursive, depth-first iterator over a directory tree, created with [`FileManager::walk`].
+///
+/// The root itself is not reported, every directory below it produces an
+/// [`EnterDir`](WalkEvent::EnterDir) event followed by the events of its children and a matching
+/// [`LeaveDir`](WalkEvent::LeaveDir) event. The paths of the entries are the root path joined
+/// with the names of the directories below it.
+///
+/// An error reading a directory is returned in place of its children, the walk continues with
+/// the directory's siblings afterwards.
+///
+/// ```
+/// use gfxfilemanager::{FileManager, MemoryArchive, WalkEvent};
+///
+/// let archive = MemoryArchive::from_files([
+///     ("media/textdata/item.txt", "item"),
+///     ("media/icon/item.ddj", "icon"),
+/// ])?;
+/// let files: Vec<String> = archive
+///     .walk("media")
+///     .filter_name("*.txt")
+///     .prune(|dir| dir.name == "icon")
+///     .filter_map(|event| match event {
+///         Ok(WalkEvent::File(entry)) => Some(entry.path),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(files, ["media/textdata/item.txt"]);
+/// # Ok::<(), gfxfilemanager::Error>(())
+/// ```
+pub struct Walk<'a, F: FileManager + 'a> {
+    file_manager: &'a F,
+    /// The root directory until it has been opened.
+    root: Option<String>,
+    /// The directories that are currently being read, `None` stands for the root.
+    stack: Vec<(Option<DirEntry>, F::ReadDir<'a>)>,
+    /// An event that has to be reported before reading on.
+    pending: Option<WalkEvent>,
+    max_depth: usize,
+    /// Length of the root path, which is stripped from the paths matched by `path_patterns`.
+    root_len: usize,
+    name_patterns: Vec<Glob>,
+    path_patterns: Vec<Glob>,
+    prune: Option<PruneFn<'a>>,
+}
+
+impl<'a, F: FileManager> Walk<'a, F> {
+    pub(crate) fn new(file_manager: &'a F, root: &str) -> Self {
+        Walk {
+            file_manager,
+            root: Some(root.to_owned()),
+            stack: Vec::new(),
+            pending: None,
+            max_depth: usize::MAX,
+            root_len: root.len(),
+            name_patterns: Vec::new(),
+            path_patterns: Vec::new(),
+            prune: None,
+        }
+    }
+
+    /// Limits how deep the walk descends, the children of the root are at depth 1 and a depth of
+    /// 0 reports nothing. Directories at the maximum depth are still entered and left, but their
+    /// children are not read.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Only reports files whose name matches one of the patterns added with this method.
+    /// Directories are always walked.
+    pub fn filter_name(mut self, pattern: impl Into<Glob>) -> Self {
+        self.name_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only reports files whose path relative to the root matches one of the patterns added with
+    /// this method, for example `**/textdata/*.txt`. Combined with [`Walk::filter_name`] a file
+    /// has to match both. Directories are always walked.
+    pub fn filter(mut self, pattern: impl Into<Glob>) -> Self {
+        self.path_patterns.push(pattern.into());
+        self
+    }
+
+    /// Skips the subtrees of directories for which the closure returns true, no events are
+    /// reported for them at all.
+    pub fn prune<P>(mut self, prune: P) -> Self
+    where
+        P: FnMut(&DirEntry) -> bool + 'a,
+    {
+        self.prune = Some(Box::new(prune));
+        self
+    }
+
+    fn is_pruned(&mut self, dir: &DirEntry) -> bool {
+        self.prune.as_mut().is_some_and(|prune| prune(dir))
+    }
+
+    fn is_included(&self, file: &DirEntry) -> bool {
+        let relative = file.path.get(self.root_len..).unwrap_or(&file.path);
+        (self.name_patterns.is_empty()
+            || self.name_patterns.iter().any(|pattern| pattern.is_match(&file.name)))
+            && (self.path_patterns.is_empty()
+                || self.path_patterns.iter().any(|pattern| pattern.is_match(relative)))
+    }
+}
+
+impl<F: FileManager> Iterator for Walk<'_, F> {
+    type Item = Result<WalkEvent>;
+
+    fn next(&mut self) -> Option<Result<WalkEvent>> {
+        if let Some(event) = self.pending.take() {
+            return Some(Ok(event));
+        }
+        if let Some(root) = self.root.take() {
+            if self.max_depth == 0 {
+                return None;
+            }
+            match self.file_manager.read_dir(&root) {
+                Ok(read_dir) => self.stack.push((None, read_dir)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        loop {
+            let (_, read_dir) = self.stack.last_mut()?;
+            let entry = match read_dir.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None => match self.stack.pop() {
+                    Some((Some(dir), _)) => return Some(Ok(WalkEvent::LeaveDir(dir))),
+                    _ => continue,
+                },
+            };
+            if entry.is_dir() {
+                if self.is_pruned(&entry) {
+                    continue;
+                }
+                if self.stack.len() < self.max_depth {
+                    match self.file_manager.read_dir(&entry.path) {
+                        Ok(read_dir) => self.stack.push((Some(entry.clone()), read_dir)),
+                        Err(e) => return Some(Err(e)),
+                    }
+                } else {
+                    self.pending = Some(WalkEvent::LeaveDir(entry.clone()));
+                }
+                return Some(Ok(WalkEvent::EnterDir(entry)));
+            }
+            if self.is_included(&entry) {
+                return Some(Ok(WalkEvent::File(entry)));
+            }
+        }
+    }
+}
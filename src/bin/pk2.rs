@@ -0,0 +1,272 @@
+//! Command line tool for working with pk2 containers without the dll.
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::ExitCode;
+
+use gfxfilemanager::pk2::recover::{KeySpace, PasswordRecovery};
+use gfxfilemanager::pk2::{ImportAction, DEFAULT_PASSWORD, LOST_AND_FOUND};
+use gfxfilemanager::{FileManager, Overwrite, Pk2};
+
+const USAGE: &str = "\
+usage: pk2 <command> [args]
+
+commands:
+    recover-password <container> --wordlist <file>
+    recover-password <container> --charset <chars> [--min-len <n>] --max-len <n>
+        Tests candidate passwords offline and prints every one that opens the container.
+    extract <container> <target> [--password <password>] [--root <dir>]
+            [--include <glob>]... [--exclude <glob>]...
+            [--overwrite always|never|newer|error] [--preserve-times true|false]
+        Copies the files below the root directory of the container into the target directory.
+    import <container> <source> [--password <password>] [--target <dir>] [--dry-run true|false]
+           [--trust-mtime true|false]
+        Copies a host file or directory into the container, skipping files that are unchanged.
+    compact <container> <output> [--password <password>]
+        Writes a copy of the container without the space left behind by deleted or replaced files.
+    verify <container> [--password <password>] [--json true|false]
+        Checks the structure of the container and fails if it found any problems.
+    repair <container> <output> [--password <password>]
+        Writes everything that can still be read from a damaged container to a new one.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("recover-password") => recover_password(&args[1..]),
+        Some("extract") => extract(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("compact") => compact(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        Some("repair") => repair(&args[1..]),
+        Some("help" | "-h" | "--help") => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        _ => Err(usage()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+type CliResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+fn usage() -> Box<dyn std::error::Error> {
+    USAGE.into()
+}
+
+/// The arguments of a command, split into positional ones and `--name value` options.
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String]) -> CliResult<Self> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = args.next().ok_or_else(|| format!("missing value for --{name}"))?;
+                    options.push((name, value.as_str()));
+                }
+                None => positional.push(arg.as_str()),
+            }
+        }
+        Ok(Args { positional, options })
+    }
+
+    /// Returns the value of the option, the last one wins if it was given multiple times.
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(n, _)| *n == name).map(|(_, value)| *value)
+    }
+
+    /// Returns all values of an option that may be given multiple times.
+    fn options(&self, name: &'a str) -> impl Iterator<Item = &'a str> + '_ {
+        self.options.iter().filter(move |(n, _)| *n == name).map(|(_, value)| *value)
+    }
+}
+
+fn recover_password(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container] = args.positional[..] else {
+        return Err(usage());
+    };
+    let recovery = PasswordRecovery::open(container)?;
+    if !recovery.header().encrypted {
+        println!("container is not encrypted, any password opens it");
+        return Ok(());
+    }
+
+    let mut found = 0;
+    if let Some(wordlist) = args.option("wordlist") {
+        for line in BufReader::new(fs::File::open(wordlist)?).lines() {
+            let line = line?;
+            let password = line.trim_end_matches('\r');
+            if recovery.check(password) {
+                println!("{password}");
+                found += 1;
+            }
+        }
+    } else if let Some(charset) = args.option("charset") {
+        let min_len = args.option("min-len").map_or(Ok(1), str::parse)?;
+        let max_len = args.option("max-len").ok_or_else(usage)?.parse()?;
+        let key_space = KeySpace::new(charset, min_len, max_len);
+        eprintln!("testing {} passwords", key_space.remaining());
+        for password in recovery.search(key_space) {
+            println!("{password}");
+            found += 1;
+        }
+    } else {
+        return Err(usage());
+    }
+
+    if found == 0 {
+        return Err("no password found".into());
+    }
+    Ok(())
+}
+
+fn extract(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container, target] = args.positional[..] else {
+        return Err(usage());
+    };
+    let overwrite = match args.option("overwrite") {
+        None | Some("always") => Overwrite::Always,
+        Some("never") => Overwrite::Never,
+        Some("newer") => Overwrite::IfNewer,
+        Some("error") => Overwrite::Error,
+        Some(other) => return Err(format!("unknown overwrite policy {other:?}").into()),
+    };
+    let archive = Pk2::open(container, args.option("password").unwrap_or(DEFAULT_PASSWORD))?;
+
+    let mut extract = archive
+        .extract(args.option("root").unwrap_or("/"), target)
+        .overwrite(overwrite)
+        .preserve_times(args.option("preserve-times").map_or(Ok(true), str::parse)?)
+        .progress(|p| eprint!("\r{}/{} files, {} bytes", p.files, p.total_files, p.bytes));
+    for pattern in args.options("include") {
+        extract = extract.include(pattern);
+    }
+    for pattern in args.options("exclude") {
+        extract = extract.exclude(pattern);
+    }
+    let summary = extract.run()?;
+    eprintln!();
+    println!(
+        "wrote {} files ({} bytes), skipped {}, created {} directories",
+        summary.written.len(),
+        summary.bytes,
+        summary.skipped.len(),
+        summary.directories
+    );
+    Ok(())
+}
+
+fn import(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container, source] = args.positional[..] else {
+        return Err(usage());
+    };
+    let dry_run = args.option("dry-run").map_or(Ok(false), str::parse)?;
+    let trust_mtime = args.option("trust-mtime").map_or(Ok(false), str::parse)?;
+    let file = fs::OpenOptions::new().read(true).write(!dry_run).open(container)?;
+    let archive = Pk2::open_in(file, args.option("password").unwrap_or(DEFAULT_PASSWORD))?;
+
+    let plan = archive
+        .import(source, args.option("target").unwrap_or("/"))
+        .dry_run(dry_run)
+        .trust_mtime(trust_mtime)
+        .progress(|p| eprint!("\r{}/{} steps, {} bytes", p.steps, p.total_steps, p.bytes))
+        .run()?;
+    eprintln!();
+    for step in &plan.steps {
+        let action = match step.action {
+            ImportAction::CreateDirectory => "mkdir",
+            ImportAction::Create => "create",
+            ImportAction::Replace => "replace",
+            ImportAction::Unchanged => continue,
+        };
+        println!("{action} {}", step.target);
+    }
+    println!(
+        "{} {} bytes, {} files unchanged",
+        if dry_run { "would write" } else { "wrote" },
+        plan.bytes(),
+        plan.filter(ImportAction::Unchanged).count()
+    );
+    Ok(())
+}
+
+fn compact(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container, output] = args.positional[..] else {
+        return Err(usage());
+    };
+    let archive = Pk2::open(container, args.option("password").unwrap_or(DEFAULT_PASSWORD))?;
+    let summary = archive.compact(output)?;
+    println!(
+        "{} directories, {} files, {} -> {} bytes, reclaimed {} bytes",
+        summary.directories,
+        summary.files,
+        summary.original_len,
+        summary.compacted_len,
+        summary.reclaimed()
+    );
+    Ok(())
+}
+
+fn verify(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container] = args.positional[..] else {
+        return Err(usage());
+    };
+    let archive = Pk2::open(container, args.option("password").unwrap_or(DEFAULT_PASSWORD))?;
+    let report = archive.verify()?;
+    if args.option("json").map_or(Ok(false), str::parse)? {
+        println!("{}", report.to_json());
+    } else {
+        for problem in &report.problems {
+            println!("{problem}");
+        }
+        println!(
+            "{} directories, {} files, {} blocks, {} problems",
+            report.directories,
+            report.files,
+            report.blocks,
+            report.problems.len()
+        );
+    }
+    if !report.is_ok() {
+        return Err(format!("found {} problems", report.problems.len()).into());
+    }
+    Ok(())
+}
+
+fn repair(args: &[String]) -> CliResult {
+    let args = Args::parse(args)?;
+    let [container, output] = args.positional[..] else {
+        return Err(usage());
+    };
+    let archive = Pk2::open(container, args.option("password").unwrap_or(DEFAULT_PASSWORD))?;
+    let summary = archive.repair(output)?;
+    println!(
+        "salvaged {} directories and {} files from {} blocks, {} directories moved to {}, \
+         {} entries dropped",
+        summary.directories,
+        summary.files,
+        summary.blocks,
+        summary.orphans,
+        LOST_AND_FOUND,
+        summary.dropped
+    );
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! The error type shared by the dll wrapper and the native implementations.
+
+use std::fmt;
+use std::io;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The error type returned by all operations of this crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading from or writing to the underlying storage failed.
+    Io(io::Error),
+    /// The password does not match the checksum stored in the container.
+    WrongPassword,
+    /// The password is empty, pk2 keys have to be 1 to 56 bytes long.
+    InvalidPassword,
+    /// Blowfish keys have to be 1 to 56 bytes long.
+    InvalidKeyLength(usize),
+    /// The length of a buffer passed to the cipher is not a multiple of the 8 byte block size.
+    UnalignedBuffer(usize),
+    /// The file does not start with the pk2 signature.
+    InvalidSignature,
+    /// No entry exists at the given path.
+    NotFound(String),
+    /// A directory was expected at the given path.
+    NotADirectory(String),
+    /// A file was expected at the given path.
+    NotAFile(String),
+    /// An entry with a different type already exists at the given path.
+    AlreadyExists(String),
+    /// The directory at the given path still contains entries.
+    DirectoryNotEmpty(String),
+    /// The name does not fit into the 81 bytes of an entry, including the null terminator.
+    NameTooLong(String),
+    /// The path is not valid for the requested operation, for example because it does not
+    /// name an entry or contains a null byte.
+    InvalidPath(String),
+    /// An entry block could not be parsed.
+    CorruptBlock { offset: u64, reason: &'static str },
+    /// A serialized index could not be read.
+    InvalidIndex(&'static str),
+    /// The file does not fit into the 32-bit size field of an entry.
+    FileTooLarge(u64),
+    /// The dll returned a mode value that is not known.
+    InvalidMode(i32),
+    /// The value is not a known access mode.
+    InvalidAccess(u32),
+    /// The dll returned an entry type that is not known.
+    InvalidEntryType(i8),
+    /// A string returned by the dll is not valid UTF-8.
+    InvalidUtf8,
+    /// The dll reported that the operation failed.
+    Dll(&'static str),
+}
+
+impl Error {
+    /// Returns the [`io::ErrorKind`] that best describes this error.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Io(e) => e.kind(),
+            Error::WrongPassword => io::ErrorKind::PermissionDenied,
+            Error::NotFound(_) => io::ErrorKind::NotFound,
+            Error::NotADirectory(_) => io::ErrorKind::NotADirectory,
+            Error::NotAFile(_) => io::ErrorKind::IsADirectory,
+            Error::AlreadyExists(_) => io::ErrorKind::AlreadyExists,
+            Error::DirectoryNotEmpty(_) => io::ErrorKind::DirectoryNotEmpty,
+            Error::FileTooLarge(_) => io::ErrorKind::FileTooLarge,
+            Error::InvalidSignature
+            | Error::CorruptBlock { .. }
+            | Error::InvalidIndex(_)
+            | Error::InvalidMode(_)
+            | Error::InvalidEntryType(_)
+            | Error::InvalidUtf8 => io::ErrorKind::InvalidData,
+            Error::InvalidPassword
+            | Error::InvalidKeyLength(_)
+            | Error::UnalignedBuffer(_)
+            | Error::NameTooLong(_)
+            | Error::InvalidPath(_)
+            | Error::InvalidAccess(_) => io::ErrorKind::InvalidInput,
+            Error::Dll(_) => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {e}"),
+            Error::WrongPassword => write!(f, "wrong password"),
+            Error::InvalidPassword => write!(f, "password is empty"),
+            Error::InvalidKeyLength(len) => {
+                write!(f, "blowfish key of {len} bytes is not 1 to 56 bytes long")
+            }
+            Error::UnalignedBuffer(len) => {
+                write!(f, "buffer of {len} bytes is not a multiple of the blowfish block size")
+            }
+            Error::InvalidSignature => write!(f, "not a pk2 container"),
+            Error::NotFound(path) => write!(f, "entry not found: {path}"),
+            Error::NotADirectory(path) => write!(f, "entry is not a directory: {path}"),
+            Error::NotAFile(path) => write!(f, "entry is not a file: {path}"),
+            Error::AlreadyExists(path) => write!(f, "entry already exists: {path}"),
+            Error::DirectoryNotEmpty(path) => write!(f, "directory is not empty: {path}"),
+            Error::NameTooLong(name) => write!(f, "entry name is too long: {name}"),
+            Error::InvalidPath(path) => write!(f, "invalid path: {path:?}"),
+            Error::CorruptBlock { offset, reason } => {
+                write!(f, "corrupt entry block at {offset:#x}: {reason}")
+            }
+            Error::InvalidIndex(reason) => write!(f, "invalid index: {reason}"),
+            Error::FileTooLarge(size) => write!(f, "file of {size} bytes exceeds the 4 GiB limit"),
+            Error::InvalidMode(mode) => write!(f, "invalid container mode {mode}"),
+            Error::InvalidAccess(access) => write!(f, "invalid access mode {access:#x}"),
+            Error::InvalidEntryType(typ) => write!(f, "invalid entry type {typ}"),
+            Error::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            Error::Dll(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Errors of this crate that were converted into an [`io::Error`], for example by a `Read`
+    /// implementation, are unwrapped again.
+    fn from(e: io::Error) -> Self {
+        if !e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return Error::Io(e);
+        }
+        *e.into_inner().and_then(|inner| inner.downcast().ok()).expect("checked to be an Error")
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
@@ -1,28 +1,39 @@
 use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
 use std::ptr::null_mut;
-use std::string::FromUtf8Error;
 
 use winapi::ctypes::{c_char, c_int, c_long, c_ulong, c_void};
-use winapi::shared::minwindef::{DWORD, HMODULE, LPDWORD, LPFILETIME};
+use winapi::shared::minwindef::{DWORD, FILETIME, HMODULE, LPDWORD, LPFILETIME};
 use winapi::shared::windef::HWND;
 
 use crate::ffi::{GFXDllCreateObject, GFXDllReleaseObject};
 
+use crate::backend::{check_names, join, FileManager};
 use crate::cjarchivefm::CJArchiveFm;
 use crate::dialog::DialogData;
+use crate::error::{Error, Result};
 use crate::gfxfile::File;
+use crate::pk2::FileTime;
 use crate::result_entry::ResultEntry;
-use crate::search_result::{GFXSearchResult, SearchResult};
+use crate::search_result::{GFXSearchResult, ReadDir, SearchResult};
 
 const OBJECT_VERSION: c_int = 0x1007;
 
 macro_rules! cstring {
     ($str: expr) => {
-        CString::new($str).unwrap()
+        CString::new($str).map_err(|_| Error::InvalidPath($str.to_owned()))?
     };
 }
 
+/// Like `cstring!`, but for paths inside the archive, whose names are checked first because the
+/// dll silently truncates names that do not fit into an entry.
+macro_rules! archive_path {
+    ($str: expr) => {{
+        check_names($str)?;
+        cstring!($str)
+    }};
+}
+
 macro_rules! vtable_call {
     ($_self:ident, $name:ident$(, $arg:expr)*) => {
         unsafe { ((*(*$_self._file_manager).vtable).$name)($_self._file_manager, $($arg),*) }
@@ -55,13 +66,13 @@ pub enum Access {
 }
 
 impl TryFrom<u32> for Access {
-    type Error = ();
-    fn try_from(mode: u32) -> Result<Self, Self::Error> {
+    type Error = Error;
+    fn try_from(mode: u32) -> Result<Self> {
         match mode {
             0 => Ok(Access::OpenExisting),
             0x8000_0000 => Ok(Access::ShareRead),
             0x4000_0000 => Ok(Access::CreateAlways),
-            _ => Err(()),
+            _ => Err(Error::InvalidAccess(mode)),
         }
     }
 }
@@ -73,12 +84,12 @@ pub enum Mode {
 }
 
 impl TryFrom<i32> for Mode {
-    type Error = ();
-    fn try_from(mode: i32) -> Result<Self, Self::Error> {
+    type Error = Error;
+    fn try_from(mode: i32) -> Result<Self> {
         match mode {
             1 => Ok(Mode::CP),
             2 => Ok(Mode::CW),
-            _ => Err(()),
+            _ => Err(Error::InvalidMode(mode)),
         }
     }
 }
@@ -101,8 +112,8 @@ impl GFXFileManager {
     }
 
     /// Returns the container-mode.
-    pub fn mode(&self) -> Mode {
-        Mode::try_from(vtable_call!(self, mode)).unwrap()
+    pub fn mode(&self) -> Result<Mode> {
+        Mode::try_from(vtable_call!(self, mode))
     }
 
     /// Sets some configuration
@@ -121,10 +132,11 @@ impl GFXFileManager {
     ///
     /// * filename - Filename of the container
     /// * password - Password for accessing the new container
-    pub fn create_container(&self, filename: &str, password: &str) -> bool {
+    pub fn create_container(&self, filename: &str, password: &str) -> Result<()> {
         let filename = cstring!(filename);
         let password = cstring!(password);
-        vtable_call!(self, create_container, filename.as_ptr(), password.as_ptr()) != 0
+        let res = vtable_call!(self, create_container, filename.as_ptr(), password.as_ptr());
+        check(res != 0, "failed to create container")
     }
 
     /// Opens an existing container
@@ -134,15 +146,16 @@ impl GFXFileManager {
     /// * filename - Filename of the container
     /// * password - Password required for accessing the container
     /// * mode - unknown, maybe for read and write access
-    pub fn open_container(&self, filename: &str, password: &str, mode: i32) -> bool {
+    pub fn open_container(&self, filename: &str, password: &str, mode: i32) -> Result<()> {
         let filename = cstring!(filename);
         let password = cstring!(password);
-        vtable_call!(self, open_container, filename.as_ptr(), password.as_ptr(), mode) != 0
+        let res = vtable_call!(self, open_container, filename.as_ptr(), password.as_ptr(), mode);
+        check(res != 0, "failed to open container")
     }
 
     /// Closes the current container
-    pub fn close_container(&self) -> bool {
-        vtable_call!(self, close_container) != 0
+    pub fn close_container(&self) -> Result<()> {
+        check(vtable_call!(self, close_container) != 0, "failed to close container")
     }
 
     /// Returns true if a container is currently open
@@ -171,18 +184,11 @@ impl GFXFileManager {
     ///
     /// * filename - Filename, relative to current dir or absolute path inside archive
     /// * unknown - Not used for original CPFileManager
-    pub fn open_file(
-        &self,
-        filename: &str,
-        access: Access,
-        unknown: i32,
-    ) -> ::std::io::Result<File> {
-        let filename = cstring!(filename);
-        let res = vtable_call!(self, open_file, filename.as_ptr(), access as i32, unknown);
-        if res == -1 {
-            Err(::std::io::Error::new(::std::io::ErrorKind::NotFound, ""))
-        } else {
-            Ok(File::new(self, res))
+    pub fn open_file(&self, filename: &str, access: Access, unknown: i32) -> Result<File<'_>> {
+        let path = archive_path!(filename);
+        match vtable_call!(self, open_file, path.as_ptr(), access as i32, unknown) {
+            -1 => Err(Error::NotFound(filename.to_owned())),
+            handle => Ok(File::new(self, handle)),
         }
     }
 
@@ -199,12 +205,12 @@ impl GFXFileManager {
         filename: &str,
         access: Access,
         unknown: i32,
-    ) -> File {
-        let filename = cstring!(filename);
-        File::new(
-            self,
-            vtable_call!(self, open_file_cj, fm, filename.as_ptr(), access as i32, unknown),
-        )
+    ) -> Result<File<'_>> {
+        let path = archive_path!(filename);
+        match vtable_call!(self, open_file_cj, fm, path.as_ptr(), access as i32, unknown) {
+            -1 => Err(Error::NotFound(filename.to_owned())),
+            handle => Ok(File::new(self, handle)),
+        }
     }
 
     pub fn function_12(&self) -> i32 {
@@ -221,9 +227,12 @@ impl GFXFileManager {
     ///
     /// * filename - Filename, relative to current dir or absolute path inside archive
     /// * unknown
-    pub fn create_file(&self, filename: &str, unknown: i32) -> File {
-        let filename = cstring!(filename);
-        File::new(self, vtable_call!(self, create_file, filename.as_ptr(), unknown))
+    pub fn create_file(&self, filename: &str, unknown: i32) -> Result<File<'_>> {
+        let filename = archive_path!(filename);
+        match vtable_call!(self, create_file, filename.as_ptr(), unknown) {
+            -1 => Err(Error::Dll("failed to create file")),
+            handle => Ok(File::new(self, handle)),
+        }
     }
 
     /// Creates a file inside the container using the CJArchiveFm-class and returns a File object
@@ -233,15 +242,23 @@ impl GFXFileManager {
     /// * fm - A mutable reference to a CJArchiveFm
     /// * filename - Filename, relative to current dir or absolute path inside archive
     /// * unknown
-    pub fn create_file_cj(&self, fm: &mut CJArchiveFm, filename: &str, unknown: i32) -> File {
-        let filename = cstring!(filename);
-        File::new(self, vtable_call!(self, create_file_cj, fm, filename.as_ptr(), unknown))
+    pub fn create_file_cj(
+        &self,
+        fm: &mut CJArchiveFm,
+        filename: &str,
+        unknown: i32,
+    ) -> Result<File<'_>> {
+        let filename = archive_path!(filename);
+        match vtable_call!(self, create_file_cj, fm, filename.as_ptr(), unknown) {
+            -1 => Err(Error::Dll("failed to create file")),
+            handle => Ok(File::new(self, handle)),
+        }
     }
 
     /// Deletes a file by name
-    pub fn delete_file(&self, filename: &str) -> i32 {
-        let filename = cstring!(filename);
-        vtable_call!(self, delete_file, filename.as_ptr())
+    pub fn delete_file(&self, filename: &str) -> Result<()> {
+        let filename = archive_path!(filename);
+        check(vtable_call!(self, delete_file, filename.as_ptr()) != 0, "failed to delete file")
     }
 
     /// Closes file by handle, not public because our handle wrapper manages its lifetime itself
@@ -304,49 +321,53 @@ impl GFXFileManager {
     }
 
     /// Creates directory in the current pk2
-    pub fn create_directory(&self, name: &str) -> bool {
-        let name = cstring!(name);
-        vtable_call!(self, create_dir, name.as_ptr()) != 0
+    pub fn create_directory(&self, name: &str) -> Result<()> {
+        let name = archive_path!(name);
+        check(vtable_call!(self, create_dir, name.as_ptr()) != 0, "failed to create directory")
     }
 
     /// Deletes directory in the current pk2
-    pub fn delete_directory(&self, name: &str) -> bool {
-        let name = cstring!(name);
-        vtable_call!(self, delete_dir, name.as_ptr()) != 0
+    pub fn delete_directory(&self, name: &str) -> Result<()> {
+        let name = archive_path!(name);
+        check(vtable_call!(self, delete_dir, name.as_ptr()) != 0, "failed to delete directory")
     }
 
     /// Resets the current working directory in the current pk2
-    pub fn reset_directory(&self) -> bool {
-        vtable_call!(self, reset_dir) != 0
+    pub fn reset_directory(&self) -> Result<()> {
+        check(vtable_call!(self, reset_dir) != 0, "failed to reset directory")
     }
 
     /// Changes the current working directory
-    pub fn change_directory(&self, name: &str) -> bool {
-        let name = cstring!(name);
-        vtable_call!(self, change_dir, name.as_ptr()) != 0
+    pub fn change_directory(&self, name: &str) -> Result<()> {
+        let path = archive_path!(name);
+        match vtable_call!(self, change_dir, path.as_ptr()) {
+            0 => Err(Error::NotADirectory(name.to_owned())),
+            _ => Ok(()),
+        }
     }
 
-    /// Returns the current directory's name or an utf8 error
-    pub fn get_directory_name(&self) -> Result<String, FromUtf8Error> {
+    /// Returns the current directory's name
+    pub fn get_directory_name(&self) -> Result<String> {
         let mut buf = vec![0; 255];
         let ptr = buf.as_mut_ptr();
         let len = vtable_call!(self, get_dir_name, 200, ptr as *mut i8);
         buf.truncate(len as usize);
-        String::from_utf8(buf)
+        String::from_utf8(buf).map_err(|_| Error::InvalidUtf8)
     }
 
-    pub fn set_virtual_path(&self, path: &str) -> bool {
+    pub fn set_virtual_path(&self, path: &str) -> Result<()> {
         let path = cstring!(path);
-        vtable_call!(self, set_virtual_path, path.as_ptr()) != 0
+        let res = vtable_call!(self, set_virtual_path, path.as_ptr());
+        check(res != 0, "failed to set virtual path")
     }
 
-    pub fn get_virtual_path(&self) -> Result<String, FromUtf8Error> {
+    pub fn get_virtual_path(&self) -> Result<String> {
         let mut buf = vec![0; 255];
         vtable_call!(self, get_virtual_path, buf.as_mut_ptr() as *mut i8);
         if let Some(null_pos) = buf.iter().position(|&x| x == 0) {
             buf.truncate(null_pos);
         }
-        String::from_utf8(buf)
+        String::from_utf8(buf).map_err(|_| Error::InvalidUtf8)
     }
 
     pub fn find_first_file(
@@ -354,20 +375,34 @@ impl GFXFileManager {
         search: &mut SearchResult,
         pattern: &str,
         entry: &mut ResultEntry,
-    ) {
+    ) -> Result<()> {
         let pattern = cstring!(pattern);
         vtable_call!(self, find_first_file, search.inner_mut(), pattern.as_ptr(), entry);
+        Ok(())
     }
 
-    pub fn find_next_file(&self, search: &mut SearchResult, entry: &mut ResultEntry) -> i32 {
-        vtable_call!(self, find_next_file, search.inner_mut(), entry)
+    /// Returns an iterator over the entries of a directory, skipping the `.` and `..` entries
+    pub fn read_dir(&self, path: &str) -> Result<ReadDir<'_>> {
+        let mut search = SearchResult::new(self);
+        let mut entry = ResultEntry::default();
+        self.find_first_file(&mut search, &join(path, "*"), &mut entry)?;
+        // every directory contains at least its `.` and `..` entries
+        if !search.success() {
+            return Err(Error::NotFound(path.to_owned()));
+        }
+        Ok(ReadDir::new(self, search, entry, path))
+    }
+
+    /// Advances the search to the next entry, returns false if there are no entries left
+    pub fn find_next_file(&self, search: &mut SearchResult, entry: &mut ResultEntry) -> bool {
+        vtable_call!(self, find_next_file, search.inner_mut(), entry) != 0
     }
 
     pub(crate) fn find_close(&self, search: &mut GFXSearchResult) -> i32 {
         vtable_call!(self, close_search_result, search)
     }
 
-    pub(crate) fn file_name_from_handle(&self, file: &File) -> Result<String, FromUtf8Error> {
+    pub(crate) fn file_name_from_handle(&self, file: &File) -> Result<String> {
         let mut buf = vec![0; 512];
         vtable_call!(
             self,
@@ -379,11 +414,14 @@ impl GFXFileManager {
         if let Some(null_pos) = buf.iter().position(|&x| x == 0) {
             buf.truncate(null_pos);
         }
-        String::from_utf8(buf)
+        String::from_utf8(buf).map_err(|_| Error::InvalidUtf8)
     }
 
-    pub(crate) fn get_file_size(&self, file: &File) -> i32 {
-        vtable_call!(self, get_file_size, file.handle(), null_mut())
+    /// Returns the size of a file, the dll reports it in two halves like `GetFileSize`.
+    pub(crate) fn get_file_size(&self, file: &File) -> u64 {
+        let mut high: DWORD = 0;
+        let low = vtable_call!(self, get_file_size, file.handle(), &mut high);
+        (u64::from(high) << 32) | u64::from(low as u32)
     }
 
     pub(crate) fn get_file_time(
@@ -426,18 +464,19 @@ impl GFXFileManager {
         dstdir: &str,
         dir_name: &str,
         create_target_dir: bool,
-    ) -> i32 {
+    ) -> Result<()> {
         let srcdir = cstring!(srcdir);
         let dstdir = cstring!(dstdir);
         let dir_name = cstring!(dir_name);
-        vtable_call!(
+        let res = vtable_call!(
             self,
             import_dir,
             srcdir.as_ptr(),
             dstdir.as_ptr(),
             dir_name.as_ptr(),
             create_target_dir
-        )
+        );
+        check(res != 0, "failed to import directory")
     }
 
     pub fn import_file(
@@ -446,18 +485,19 @@ impl GFXFileManager {
         dstdir: &str,
         filename: &str,
         create_target_dir: bool,
-    ) -> i32 {
+    ) -> Result<()> {
         let srcdir = cstring!(srcdir);
         let dstdir = cstring!(dstdir);
         let filename = cstring!(filename);
-        vtable_call!(
+        let res = vtable_call!(
             self,
             import_file,
             srcdir.as_ptr(),
             dstdir.as_ptr(),
             filename.as_ptr(),
             create_target_dir
-        )
+        );
+        check(res != 0, "failed to import file")
     }
 
     pub fn export_directory(
@@ -466,18 +506,19 @@ impl GFXFileManager {
         dstdir: &str,
         dir_name: &str,
         create_target_dir: bool,
-    ) -> i32 {
+    ) -> Result<()> {
         let srcdir = cstring!(srcdir);
         let dstdir = cstring!(dstdir);
         let dir_name = cstring!(dir_name);
-        vtable_call!(
+        let res = vtable_call!(
             self,
             export_dir,
             srcdir.as_ptr(),
             dstdir.as_ptr(),
             dir_name.as_ptr(),
             create_target_dir
-        )
+        );
+        check(res != 0, "failed to export directory")
     }
 
     pub fn export_file(
@@ -486,37 +527,42 @@ impl GFXFileManager {
         dstdir: &str,
         filename: &str,
         create_target_dir: bool,
-    ) -> i32 {
+    ) -> Result<()> {
         let srcdir = cstring!(srcdir);
         let dstdir = cstring!(dstdir);
         let filename = cstring!(filename);
-        vtable_call!(
+        let res = vtable_call!(
             self,
             export_file,
             srcdir.as_ptr(),
             dstdir.as_ptr(),
             filename.as_ptr(),
             create_target_dir
-        )
+        );
+        check(res != 0, "failed to export file")
     }
 
-    pub fn file_exists(&self, name: &str, flags: i32) -> i32 {
-        let name = cstring!(name);
-        vtable_call!(self, file_exists, name.as_ptr(), flags)
+    pub fn file_exists(&self, name: &str, flags: i32) -> Result<bool> {
+        let name = archive_path!(name);
+        Ok(vtable_call!(self, file_exists, name.as_ptr(), flags) != 0)
     }
 
     pub fn show_dialog(&self, data: &mut DialogData) -> i32 {
         vtable_call!(self, show_dialog, data)
     }
 
+    /// Raw callback based iteration of the dll, [`FileManager::walk`](crate::FileManager::walk)
+    /// is the safe alternative that works with closures and iterators.
     pub fn for_each_entry_in_container(
         &self,
         callback: ForEachCallback,
         filter: &str,
         userstate: *mut c_void,
-    ) -> i32 {
+    ) -> Result<()> {
         let filter = cstring!(filter);
-        vtable_call!(self, for_each_entry_in_container, callback, filter.as_ptr(), userstate)
+        let res =
+            vtable_call!(self, for_each_entry_in_container, callback, filter.as_ptr(), userstate);
+        check(res != 0, "failed to iterate the container")
     }
 
     pub fn update_current_directory(&self) -> i32 {
@@ -548,11 +594,72 @@ impl Drop for GFXFileManager {
     fn drop(&mut self) {
         self.close_all_files();
         if self.is_open() {
-            self.close_container();
+            let _ = self.close_container();
         }
     }
 }
 
+impl FileManager for GFXFileManager {
+    type File<'a> = File<'a>;
+    type FileMut<'a> = File<'a>;
+    type ReadDir<'a> = ReadDir<'a>;
+
+    fn open_file(&self, path: &str) -> Result<File<'_>> {
+        GFXFileManager::open_file(self, path, Access::OpenExisting, 0)
+    }
+
+    fn create_file(&self, path: &str) -> Result<File<'_>> {
+        GFXFileManager::create_file(self, path, 0)
+    }
+
+    fn delete_file(&self, path: &str) -> Result<()> {
+        GFXFileManager::delete_file(self, path)
+    }
+
+    fn create_directory(&self, path: &str) -> Result<()> {
+        GFXFileManager::create_directory(self, path)
+    }
+
+    fn delete_directory(&self, path: &str) -> Result<()> {
+        GFXFileManager::delete_directory(self, path)
+    }
+
+    fn change_directory(&mut self, path: &str) -> Result<()> {
+        GFXFileManager::change_directory(self, path)
+    }
+
+    fn reset_directory(&mut self) -> Result<()> {
+        GFXFileManager::reset_directory(self)
+    }
+
+    fn current_directory(&self) -> Result<String> {
+        self.get_directory_name()
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool> {
+        GFXFileManager::file_exists(self, path, 0)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<ReadDir<'_>> {
+        GFXFileManager::read_dir(self, path)
+    }
+}
+
+impl From<FILETIME> for FileTime {
+    fn from(time: FILETIME) -> Self {
+        FileTime(u64::from(time.dwHighDateTime) << 32 | u64::from(time.dwLowDateTime))
+    }
+}
+
+/// Turns the status returned by the dll into an error, nonzero values mean success.
+fn check(success: bool, msg: &'static str) -> Result<()> {
+    if success {
+        Ok(())
+    } else {
+        Err(Error::Dll(msg))
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 struct VTable {
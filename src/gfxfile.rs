@@ -1,18 +1,21 @@
-use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::io::{self, Read, Result, Seek, SeekFrom, Write};
 
-use winapi::ctypes::c_int;
+use winapi::ctypes::{c_int, c_long};
 use winapi::shared::minwindef::{FILETIME, LPFILETIME};
 
+use crate::error;
 use crate::file_manager::GFXFileManager;
 
 pub struct File<'a> {
     handle: c_int,
     file_manager: &'a GFXFileManager,
+    /// The position of the dll's file pointer, tracked so that it can be queried without a call.
+    pos: u64,
 }
 
 impl<'fm> File<'fm> {
     pub fn new(file_manager: &'fm GFXFileManager, handle: c_int) -> File<'fm> {
-        File { handle, file_manager }
+        File { handle, file_manager, pos: 0 }
     }
 
     #[inline(always)]
@@ -21,7 +24,7 @@ impl<'fm> File<'fm> {
     }
 
     pub fn len(&self) -> u64 {
-        self.file_manager.get_file_size(self) as u64
+        self.file_manager.get_file_size(self)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -46,25 +49,55 @@ impl<'fm> File<'fm> {
         self.file_manager.set_file_time(self, creation_time, last_write_time);
     }
 
-    pub fn name(&self) -> String {
-        self.file_manager.file_name_from_handle(self).unwrap()
+    pub fn name(&self) -> error::Result<String> {
+        self.file_manager.file_name_from_handle(self)
     }
 }
 
 impl Read for File<'_> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let len = buf.len();
+        // the dll takes the length as an i32, larger buffers are filled partially
+        let len = buf.len().min(i32::MAX as usize) as i32;
         let mut bytes_read = 0;
-        self.file_manager.read(self, buf, len as i32, &mut bytes_read);
+        if self.file_manager.read(self, buf, len, &mut bytes_read) == 0 {
+            return Err(error::Error::Dll("failed to read from file").into());
+        }
+        self.pos += u64::from(bytes_read);
         Ok(bytes_read as usize)
     }
+
+    /// Sizes the buffer for the rest of the file up front, so it is read with as few calls into
+    /// the dll as possible and allocated only once.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let remaining = usize::try_from(self.len().saturating_sub(self.pos)).unwrap_or(0);
+        buf.resize(start + remaining, 0);
+        let mut filled = start;
+        let result = loop {
+            if filled == buf.len() {
+                // make sure the end was reached, the file may have grown since checking its size
+                buf.resize(filled + 4096, 0);
+            }
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => break Ok(filled - start),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        buf.truncate(filled);
+        result
+    }
 }
 
 impl Write for File<'_> {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let len = buf.len();
+        let len = buf.len().min(i32::MAX as usize) as i32;
         let mut bytes_written = 0;
-        self.file_manager.write(self, buf, len as i32, &mut bytes_written);
+        if self.file_manager.write(self, buf, len, &mut bytes_written) == 0 {
+            return Err(error::Error::Dll("failed to write to file").into());
+        }
+        self.pos += u64::from(bytes_written);
         Ok(bytes_written as usize)
     }
 
@@ -75,15 +108,33 @@ impl Write for File<'_> {
 }
 
 impl Seek for File<'_> {
+    /// The dll only takes a signed 32-bit distance, seeks that do not fit into it fail instead
+    /// of being truncated. The new position is reported as an unsigned 32-bit value, which
+    /// covers every file since entry sizes are 32-bit as well.
     fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
-        let (move_method, distance_to_move) = {
-            match pos {
-                SeekFrom::Start(u) => (0, u as i32),
-                SeekFrom::Current(i) => (1, i as i32),
-                SeekFrom::End(i) => (2, i as i32),
-            }
+        let (move_method, distance) = match pos {
+            SeekFrom::Start(offset) => (0, c_long::try_from(offset).ok()),
+            SeekFrom::Current(offset) => (1, c_long::try_from(offset).ok()),
+            SeekFrom::End(offset) => (2, c_long::try_from(offset).ok()),
         };
-        Ok(self.file_manager.seek(self, distance_to_move, move_method) as u64)
+        let distance = distance.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek distance exceeds the range of the dll",
+            )
+        })?;
+        match self.file_manager.seek(self, distance, move_method) {
+            // INVALID_SET_FILE_POINTER
+            -1 => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek")),
+            new_pos => {
+                self.pos = u64::from(new_pos as u32);
+                Ok(self.pos)
+            }
+        }
+    }
+
+    fn stream_position(&mut self) -> Result<u64> {
+        Ok(self.pos)
     }
 }
 
@@ -1,23 +1,49 @@
-#![cfg(all(target_os = "windows", target_arch = "x86"))]
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 extern crate winapi;
 
+pub mod backend;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod cjarchivefm;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod dialog;
+pub mod error;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod file_manager;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod gfxfile;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod gfxinfo;
+pub mod pk2;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod result_entry;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub mod search_result;
 
+pub use backend::{
+    ArchivePath, DirEntry, Extract, FileManager, Glob, HostFileManager, Index, MemoryArchive,
+    Overwrite, Walk, WalkEvent,
+};
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use cjarchivefm::CJArchiveFm;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use dialog::DialogData;
+pub use error::{Error, Result};
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use file_manager::{Access, CallbackState, GFXFileManager, Mode, UnknownPair};
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use file_manager::{ErrorHandler, ForEachCallback};
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use gfxfile::File;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use gfxinfo::GFXInfo;
+pub use pk2::Pk2;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use result_entry::{Entry, ResultEntry};
-pub use search_result::SearchResult;
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
+pub use search_result::{ReadDir, SearchResult};
 
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 pub use winapi::shared::windef::HWND;
 
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
 mod ffi;
@@ -0,0 +1,248 @@
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWriteExt, ReadBuf};
+use tokio::task::JoinHandle;
+
+use crate::backend::{DirEntry, Extract, ExtractSummary, FileManager};
+use crate::error::{Error, Result};
+use crate::pk2::Pk2;
+
+/// Upper bound for the bytes fetched by a single read of an [`AsyncFile`].
+const MAX_READ: usize = 64 * 1024;
+
+/// A container for use from async code.
+///
+/// Entries are looked up and file data is read on tokio's blocking thread pool, using the
+/// positional reads of [`Pk2`], so any number of [`AsyncFile`]s can be read concurrently without
+/// blocking the runtime. The archive is cheap to clone, all clones share the same handles.
+#[derive(Clone)]
+pub struct AsyncPk2 {
+    archive: Arc<Pk2>,
+}
+
+impl AsyncPk2 {
+    /// Opens an existing container
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path of the container on the host filesystem
+    /// * password - Password required for accessing the container
+    pub async fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let password = password.to_owned();
+        spawn_blocking(move || {
+            let archive = Pk2::open(path, &password)?;
+            Ok(AsyncPk2 { archive: Arc::new(archive) })
+        })
+        .await
+    }
+
+    /// Returns the underlying archive for blocking access.
+    pub fn archive(&self) -> &Pk2 {
+        &self.archive
+    }
+
+    /// Opens a file for reading.
+    pub async fn open_file(&self, path: &str) -> Result<AsyncFile> {
+        let archive = Arc::clone(&self.archive);
+        let path = path.to_owned();
+        let entry = spawn_blocking(move || archive.open_entry(&path)).await?;
+        Ok(AsyncFile {
+            archive: Arc::clone(&self.archive),
+            name: entry.name,
+            offset: entry.position,
+            len: u64::from(entry.size),
+            pos: 0,
+            read: None,
+        })
+    }
+
+    /// Returns the entries of a directory, without `.` and `..`.
+    pub async fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>> {
+        let archive = Arc::clone(&self.archive);
+        let path = path.to_owned();
+        spawn_blocking(move || archive.read_dir(&path)?.collect()).await
+    }
+
+    /// Reads the whole file at the given path.
+    pub async fn bytes(&self, path: &str) -> Result<Vec<u8>> {
+        let file = self.open_file(path).await?;
+        let (archive, offset, len) = (Arc::clone(&file.archive), file.offset, file.len);
+        spawn_blocking(move || {
+            let mut buf = vec![0; usize::try_from(len).map_err(|_| Error::FileTooLarge(len))?];
+            archive.read_at(offset, &mut buf)?;
+            Ok(buf)
+        })
+        .await
+    }
+
+    /// Copies the directory tree below `root` into the directory `target` on the host
+    /// filesystem with the default options of [`Extract`], see [`AsyncPk2::extract_with`].
+    pub async fn extract<P: AsRef<Path>>(&self, root: &str, target: P) -> Result<ExtractSummary> {
+        self.extract_with(root, target, |extract| extract).await
+    }
+
+    /// Copies the directory tree below `root` into the directory `target` on the host
+    /// filesystem. The extraction runs on the blocking thread pool, `configure` receives the
+    /// [`Extract`] there to set filters, the overwrite policy or a progress callback.
+    ///
+    /// ```no_run
+    /// use gfxfilemanager::pk2::AsyncPk2;
+    /// use gfxfilemanager::Overwrite;
+    ///
+    /// # async fn run(archive: AsyncPk2) -> gfxfilemanager::Result<()> {
+    /// let summary = archive
+    ///     .extract_with("Media", "client/Media", |extract| {
+    ///         extract.exclude("**/*.ddj").overwrite(Overwrite::IfNewer)
+    ///     })
+    ///     .await?;
+    /// println!("wrote {} files", summary.written.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn extract_with<P, F>(
+        &self,
+        root: &str,
+        target: P,
+        configure: F,
+    ) -> Result<ExtractSummary>
+    where
+        P: AsRef<Path>,
+        F: for<'a> FnOnce(Extract<'a, Pk2>) -> Extract<'a, Pk2> + Send + 'static,
+    {
+        let archive = Arc::clone(&self.archive);
+        let (root, target) = (root.to_owned(), target.as_ref().to_owned());
+        spawn_blocking(move || configure(archive.extract(&root, target)).run()).await
+    }
+
+    /// Copies a file from the container to the host filesystem, creating missing parent
+    /// directories and overwriting an existing file. Returns the number of bytes copied.
+    pub async fn extract_file<P: AsRef<Path>>(&self, path: &str, dst: P) -> Result<u64> {
+        let dst = dst.as_ref();
+        let mut src = self.open_file(path).await?;
+        if let Some(parent) = dst.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut dst = tokio::fs::File::create(dst).await?;
+        let copied = tokio::io::copy(&mut src, &mut dst).await?;
+        dst.flush().await?;
+        Ok(copied)
+    }
+}
+
+/// A file inside an [`AsyncPk2`] opened for reading.
+///
+/// Every file has its own position and reads its data at absolute offsets of the container, so
+/// files of the same archive can be read concurrently.
+pub struct AsyncFile {
+    archive: Arc<Pk2>,
+    name: String,
+    /// Offset of the payload inside the container.
+    offset: u64,
+    len: u64,
+    pos: u64,
+    /// The read in progress and the position it started at.
+    read: Option<(u64, JoinHandle<io::Result<Vec<u8>>>)>,
+}
+
+impl AsyncFile {
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Drop for AsyncFile {
+    fn drop(&mut self) {
+        self.archive.free_list().close(self.offset..self.offset.saturating_add(self.len));
+    }
+}
+
+impl AsyncRead for AsyncFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((pos, handle)) = &mut this.read {
+                let result = ready!(Pin::new(handle).poll(cx));
+                let pos = *pos;
+                this.read = None;
+                let data = result??;
+                // a read started before a seek is discarded
+                if pos == this.pos {
+                    let n = data.len().min(buf.remaining());
+                    buf.put_slice(&data[..n]);
+                    this.pos += n as u64;
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            let remaining = this.len.saturating_sub(this.pos);
+            let n =
+                buf.remaining().min(MAX_READ).min(usize::try_from(remaining).unwrap_or(MAX_READ));
+            if n == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            let offset = this.offset.checked_add(this.pos).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "file data extends past the 64-bit range",
+                )
+            })?;
+            let archive = Arc::clone(&this.archive);
+            let handle = tokio::task::spawn_blocking(move || {
+                let mut data = vec![0; n];
+                archive.read_at(offset, &mut data)?;
+                Ok(data)
+            });
+            this.read = Some((this.pos, handle));
+        }
+    }
+}
+
+impl AsyncSeek for AsyncFile {
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => this.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => this.len.checked_add_signed(offset),
+        };
+        this.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        this.read = None;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
+
+/// Runs a blocking operation on tokio's blocking thread pool.
+async fn spawn_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|e| Error::Io(e.into()))?
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::pk2::entry::{
+    EntryKind, PackBlock, PackEntry, BLOCK_SIZE, ENTRIES_PER_BLOCK, ENTRY_SIZE,
+};
+use crate::pk2::{Pk2, HEADER_SIZE, ROOT_BLOCK};
+
+/// The outcome of [`Pk2::compact_to`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactSummary {
+    /// Size of the source container in bytes.
+    pub original_len: u64,
+    /// Size of the compacted container in bytes.
+    pub compacted_len: u64,
+    pub directories: usize,
+    pub files: usize,
+}
+
+impl CompactSummary {
+    /// Returns the number of bytes the compacted container is smaller than the source.
+    pub fn reclaimed(&self) -> u64 {
+        self.original_len.saturating_sub(self.compacted_len)
+    }
+}
+
+/// What the position of an entry refers to in the compacted container.
+enum Target {
+    /// The `.` entry, the directory's own first block.
+    Itself,
+    /// The `..` entry, the first block of the parent directory.
+    Parent,
+    /// The first block of the directory at this index of the layout.
+    Directory(usize),
+    /// The payload of a file at the given offset of the source container.
+    Payload { source: u64, size: u32 },
+}
+
+struct Slot {
+    /// The entry as stored in the source, so names that are not valid UTF-8 are kept.
+    raw: [u8; ENTRY_SIZE],
+    target: Target,
+}
+
+/// A directory of the compacted container.
+struct Directory {
+    parent: usize,
+    slots: Vec<Slot>,
+    first_block: u64,
+}
+
+impl Directory {
+    fn blocks(&self) -> usize {
+        self.slots.len().div_ceil(ENTRIES_PER_BLOCK).max(1)
+    }
+}
+
+impl<B: Read + Seek> Pk2<B> {
+    /// Writes a compacted copy of the container to a new file at `path`, which must not exist
+    /// yet. See [`Pk2::compact_to`].
+    pub fn compact<P: AsRef<Path>>(&self, path: P) -> Result<CompactSummary> {
+        let file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut dst = BufWriter::new(file);
+        let summary = self.compact_to(&mut dst)?;
+        dst.flush()?;
+        Ok(summary)
+    }
+
+    /// Writes a copy of the container that only contains the entries reachable from the root.
+    ///
+    /// All entry blocks follow the header, with the entries of every directory packed into as
+    /// few blocks as possible, and are followed by the payloads in the same order. Space left
+    /// behind by deleted or replaced files and empty slots is dropped. The header, names and
+    /// timestamps are copied unchanged.
+    ///
+    /// Only the entries are kept in memory, payloads are copied in chunks, and the output is
+    /// written strictly sequentially, so it does not need to be seekable.
+    pub fn compact_to<W: Write>(&self, mut dst: W) -> Result<CompactSummary> {
+        let mut dirs = Vec::new();
+        self.collect_directory(ROOT_BLOCK, 0, &mut dirs, &mut HashSet::new())?;
+
+        let mut offset = HEADER_SIZE as u64;
+        for dir in &mut dirs {
+            dir.first_block = offset;
+            offset += (dir.blocks() * BLOCK_SIZE) as u64;
+        }
+
+        let mut header = [0; HEADER_SIZE];
+        self.read_at(0, &mut header)?;
+        dst.write_all(&header)?;
+
+        let mut summary = CompactSummary {
+            original_len: self.stream().seek(SeekFrom::End(0))?,
+            directories: dirs.len() - 1,
+            ..CompactSummary::default()
+        };
+        let mut payload_offset = offset;
+        for dir in &dirs {
+            let mut blocks: Vec<_> = (0..dir.blocks())
+                .map(|i| PackBlock::empty(dir.first_block + (i * BLOCK_SIZE) as u64))
+                .collect();
+            for (i, slot) in dir.slots.iter().enumerate() {
+                let position = match slot.target {
+                    Target::Itself => dir.first_block,
+                    Target::Parent => dirs[dir.parent].first_block,
+                    Target::Directory(child) => dirs[child].first_block,
+                    Target::Payload { size, .. } => {
+                        let position = payload_offset;
+                        payload_offset += u64::from(size);
+                        summary.files += 1;
+                        position
+                    }
+                };
+                blocks[i / ENTRIES_PER_BLOCK].set_raw_entry(
+                    i % ENTRIES_PER_BLOCK,
+                    slot.raw,
+                    position,
+                );
+            }
+            for i in 1..blocks.len() {
+                let next = blocks[i].offset;
+                blocks[i - 1].set_next_block(next);
+            }
+            for block in &blocks {
+                let mut buf = *block.raw();
+                if let Some(blowfish) = &self.blowfish {
+                    blowfish.encrypt(&mut buf)?;
+                }
+                dst.write_all(&buf)?;
+            }
+        }
+
+        let mut buf = vec![0; 64 * 1024];
+        for slot in dirs.iter().flat_map(|dir| &dir.slots) {
+            if let Target::Payload { source, size } = slot.target {
+                let (mut source, mut remaining) = (source, u64::from(size));
+                while remaining > 0 {
+                    let n = buf.len().min(remaining as usize);
+                    self.read_at(source, &mut buf[..n])?;
+                    dst.write_all(&buf[..n])?;
+                    source += n as u64;
+                    remaining -= n as u64;
+                }
+            }
+        }
+        summary.compacted_len = payload_offset;
+        Ok(summary)
+    }
+
+    /// Adds the directory whose first block is at `source` and everything below it to the
+    /// layout, returning its index. Every directory starts with its `.` and `..` entries, they
+    /// are created if the source lacks them.
+    fn collect_directory(
+        &self,
+        source: u64,
+        parent: usize,
+        dirs: &mut Vec<Directory>,
+        visited: &mut HashSet<u64>,
+    ) -> Result<usize> {
+        if !visited.insert(source) {
+            return Err(Error::CorruptBlock {
+                offset: source,
+                reason: "directory contains itself",
+            });
+        }
+        let idx = dirs.len();
+        dirs.push(Directory { parent, slots: Vec::new(), first_block: 0 });
+
+        let (mut itself, mut parent) = (None, None);
+        let mut slots = Vec::new();
+        let mut children = Vec::new();
+        for block in self.read_chain(source)? {
+            for (i, entry) in block.entries.iter().enumerate() {
+                let raw = block.raw_entry(i);
+                match entry.kind {
+                    EntryKind::Empty => {}
+                    EntryKind::Directory if entry.name == "." => {
+                        itself.get_or_insert(raw);
+                    }
+                    EntryKind::Directory if entry.name == ".." => {
+                        parent.get_or_insert(raw);
+                    }
+                    EntryKind::Directory => {
+                        children.push((slots.len() + 2, entry.position));
+                        slots.push(Slot { raw, target: Target::Directory(0) });
+                    }
+                    EntryKind::File => {
+                        let target = Target::Payload { source: entry.position, size: entry.size };
+                        slots.push(Slot { raw, target });
+                    }
+                }
+            }
+        }
+        let link = |name| {
+            let mut raw = [0; ENTRY_SIZE];
+            PackEntry::new(EntryKind::Directory, name, 0, 0).write_to(&mut raw);
+            raw
+        };
+        slots.splice(
+            0..0,
+            [
+                Slot { raw: itself.unwrap_or_else(|| link(".")), target: Target::Itself },
+                Slot { raw: parent.unwrap_or_else(|| link("..")), target: Target::Parent },
+            ],
+        );
+
+        for (slot, position) in children {
+            slots[slot].target =
+                Target::Directory(self.collect_directory(position, idx, dirs, visited)?);
+        }
+        dirs[idx].slots = slots;
+        Ok(idx)
+    }
+}
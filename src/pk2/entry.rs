@@ -0,0 +1,216 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+pub(crate) const ENTRY_SIZE: usize = 128;
+pub(crate) const ENTRIES_PER_BLOCK: usize = 20;
+pub(crate) const BLOCK_SIZE: usize = ENTRY_SIZE * ENTRIES_PER_BLOCK;
+/// Maximum length of an entry name, excluding the terminating null byte.
+pub const MAX_NAME_LEN: usize = 80;
+
+/// A windows FILETIME, the number of 100ns intervals since 1601-01-01.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileTime(pub u64);
+
+impl FileTime {
+    /// Number of 100ns intervals between 1601-01-01 and 1970-01-01.
+    const UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+    pub fn now() -> Self {
+        FileTime::from(SystemTime::now())
+    }
+
+    /// Converts the time to a [`SystemTime`], returns `None` if it cannot be represented, as
+    /// happens with the garbage found in damaged containers.
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        if self.0 >= Self::UNIX_EPOCH {
+            let nanos = (self.0 - Self::UNIX_EPOCH).checked_mul(100)?;
+            UNIX_EPOCH.checked_add(Duration::from_nanos(nanos))
+        } else {
+            let nanos = (Self::UNIX_EPOCH - self.0).checked_mul(100)?;
+            UNIX_EPOCH.checked_sub(Duration::from_nanos(nanos))
+        }
+    }
+}
+
+impl From<SystemTime> for FileTime {
+    fn from(time: SystemTime) -> Self {
+        let intervals = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => Self::UNIX_EPOCH.saturating_add((since.as_nanos() / 100) as u64),
+            Err(e) => Self::UNIX_EPOCH.saturating_sub((e.duration().as_nanos() / 100) as u64),
+        };
+        FileTime(intervals)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    Empty = 0,
+    Directory = 1,
+    File = 2,
+}
+
+/// A single 128 byte record of an entry block.
+#[derive(Clone, Debug)]
+pub(crate) struct PackEntry {
+    pub kind: EntryKind,
+    /// The name for display and lookups, bytes that are not valid UTF-8 are replaced.
+    pub name: String,
+    /// The name as stored, without the null terminator. It is written back as it is, so names
+    /// in other encodings, like the EUC-KR names of client containers, survive modifications.
+    pub name_raw: Vec<u8>,
+    pub access_time: FileTime,
+    pub create_time: FileTime,
+    pub modify_time: FileTime,
+    /// Offset of the first child block for directories, offset of the payload for files.
+    pub position: u64,
+    pub size: u32,
+    /// Offset of the next block of the directory, only used by the last entry of a block.
+    pub next_block: u64,
+}
+
+impl PackEntry {
+    /// Creates an entry with all of its timestamps set to now.
+    pub(crate) fn new(kind: EntryKind, name: &str, position: u64, size: u32) -> Self {
+        let now = FileTime::now();
+        PackEntry {
+            kind,
+            name: name.to_owned(),
+            name_raw: name.as_bytes().to_vec(),
+            access_time: now,
+            create_time: now,
+            modify_time: now,
+            position,
+            size,
+            next_block: 0,
+        }
+    }
+
+    /// Creates an entry marking an unused slot.
+    pub(crate) fn empty() -> Self {
+        PackEntry {
+            kind: EntryKind::Empty,
+            name: String::new(),
+            name_raw: Vec::new(),
+            access_time: FileTime::default(),
+            create_time: FileTime::default(),
+            modify_time: FileTime::default(),
+            position: 0,
+            size: 0,
+            next_block: 0,
+        }
+    }
+
+    /// Parses a 128 byte slot, the error describes why the slot is invalid.
+    pub(crate) fn parse(buf: &[u8]) -> Result<Self, &'static str> {
+        let kind = match buf[0] {
+            0 => EntryKind::Empty,
+            1 => EntryKind::Directory,
+            2 => EntryKind::File,
+            _ => return Err("invalid entry type"),
+        };
+        let name = &buf[1..82];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        let u64_at = |at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+        Ok(PackEntry {
+            kind,
+            name: String::from_utf8_lossy(name).into_owned(),
+            name_raw: name.to_vec(),
+            access_time: FileTime(u64_at(82)),
+            create_time: FileTime(u64_at(90)),
+            modify_time: FileTime(u64_at(98)),
+            position: u64_at(106),
+            size: u32::from_le_bytes(buf[114..118].try_into().unwrap()),
+            next_block: u64_at(118),
+        })
+    }
+
+    /// Serializes the entry into a 128 byte slot, the name is expected to have been validated.
+    pub(crate) fn write_to(&self, buf: &mut [u8]) {
+        buf.fill(0);
+        buf[0] = self.kind as u8;
+        buf[1..1 + self.name_raw.len()].copy_from_slice(&self.name_raw);
+        buf[82..90].copy_from_slice(&self.access_time.0.to_le_bytes());
+        buf[90..98].copy_from_slice(&self.create_time.0.to_le_bytes());
+        buf[98..106].copy_from_slice(&self.modify_time.0.to_le_bytes());
+        buf[106..114].copy_from_slice(&self.position.to_le_bytes());
+        buf[114..118].copy_from_slice(&self.size.to_le_bytes());
+        buf[118..126].copy_from_slice(&self.next_block.to_le_bytes());
+    }
+
+    /// Renames the entry, replacing the stored bytes of the name.
+    pub(crate) fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+        self.name_raw = name.as_bytes().to_vec();
+    }
+
+    pub(crate) fn is_named(&self, name: &str) -> bool {
+        self.kind != EntryKind::Empty && self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+/// A block of entries as stored in the container.
+///
+/// The decrypted bytes are kept around so that rewriting a block only touches the entries that
+/// were modified, names that are not valid UTF-8 survive that way.
+pub(crate) struct PackBlock {
+    pub offset: u64,
+    pub entries: Vec<PackEntry>,
+    raw: [u8; BLOCK_SIZE],
+}
+
+impl PackBlock {
+    pub(crate) fn parse(offset: u64, raw: [u8; BLOCK_SIZE]) -> Result<Self> {
+        let entries = raw
+            .chunks_exact(ENTRY_SIZE)
+            .map(PackEntry::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|reason| Error::CorruptBlock { offset, reason })?;
+        Ok(PackBlock { offset, entries, raw })
+    }
+
+    /// Creates a block consisting of empty entries only.
+    pub(crate) fn empty(offset: u64) -> Self {
+        PackBlock::parse(offset, [0; BLOCK_SIZE]).unwrap()
+    }
+
+    pub(crate) fn raw(&self) -> &[u8; BLOCK_SIZE] {
+        &self.raw
+    }
+
+    /// Replaces the entry at `idx`, the link to the next block is kept as is.
+    pub(crate) fn set_entry(&mut self, idx: usize, mut entry: PackEntry) {
+        entry.next_block = self.entries[idx].next_block;
+        entry.write_to(&mut self.raw[idx * ENTRY_SIZE..(idx + 1) * ENTRY_SIZE]);
+        self.entries[idx] = entry;
+    }
+
+    /// Returns the bytes of the entry at `idx`, including a name that is not valid UTF-8.
+    pub(crate) fn raw_entry(&self, idx: usize) -> [u8; ENTRY_SIZE] {
+        self.raw[idx * ENTRY_SIZE..(idx + 1) * ENTRY_SIZE].try_into().unwrap()
+    }
+
+    /// Stores the bytes of an entry taken from another block with a new position, the link to
+    /// the next block is cleared.
+    pub(crate) fn set_raw_entry(&mut self, idx: usize, mut raw: [u8; ENTRY_SIZE], position: u64) {
+        raw[106..114].copy_from_slice(&position.to_le_bytes());
+        raw[118..126].fill(0);
+        self.entries[idx] = PackEntry::parse(&raw).expect("entry was parsed before");
+        self.raw[idx * ENTRY_SIZE..(idx + 1) * ENTRY_SIZE].copy_from_slice(&raw);
+    }
+
+    /// Returns the offset of the block following this one in the directory chain.
+    pub(crate) fn next_block(&self) -> Option<u64> {
+        match self.entries[ENTRIES_PER_BLOCK - 1].next_block {
+            0 => None,
+            offset => Some(offset),
+        }
+    }
+
+    pub(crate) fn set_next_block(&mut self, offset: u64) {
+        let last = ENTRIES_PER_BLOCK - 1;
+        self.entries[last].next_block = offset;
+        let at = last * ENTRY_SIZE + 118;
+        self.raw[at..at + 8].copy_from_slice(&offset.to_le_bytes());
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Error, Result};
+use crate::pk2::entry::{FileTime, PackEntry};
+use crate::pk2::write::payload;
+use crate::pk2::Pk2;
+
+/// A file inside a [`Pk2`] container, opened with [`Pk2::open_file`].
+pub struct File<'pk2, B = fs::File> {
+    archive: &'pk2 Pk2<B>,
+    entry: PackEntry,
+    pos: u64,
+}
+
+impl<'pk2, B: Read + Seek> File<'pk2, B> {
+    /// Creates a handle for an entry returned by [`Pk2::open_entry`], which is closed on drop.
+    pub(crate) fn new(archive: &'pk2 Pk2<B>, entry: PackEntry) -> Self {
+        File { archive, entry, pos: 0 }
+    }
+
+    pub fn len(&self) -> u64 {
+        u64::from(self.entry.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the creation and last write time of this file
+    pub fn file_time(&self) -> (FileTime, FileTime) {
+        (self.entry.create_time, self.entry.modify_time)
+    }
+
+    pub fn access_time(&self) -> FileTime {
+        self.entry.access_time
+    }
+
+    pub fn name(&self) -> &str {
+        &self.entry.name
+    }
+
+    /// Reads from the given offset of the file without moving its position, like
+    /// `FileExt::read_at` on unix. Returns the number of bytes read, which is only less than the
+    /// length of the buffer at the end of the file.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let remaining = self.len().saturating_sub(offset);
+        let n = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        if n == 0 {
+            return Ok(0);
+        }
+        let offset = self.entry.position.checked_add(offset).ok_or(Error::CorruptBlock {
+            offset: self.entry.position,
+            reason: "file data extends past the 64-bit range",
+        })?;
+        self.archive.read_at(offset, &mut buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl<B: Read + Seek> Read for File<'_, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<B> Drop for File<'_, B> {
+    fn drop(&mut self) {
+        self.archive.free_list().close(payload(&self.entry));
+    }
+}
+
+impl<B: Read + Seek> Seek for File<'_, B> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// A file inside a [`Pk2`] container opened for writing with [`Pk2::create_file`].
+///
+/// Written data is buffered in memory and stored in the container on [`Write::flush`] or when
+/// the handle is dropped. Errors that occur while dropping are ignored, so call `flush` to
+/// handle them. The entry is looked up again by its path whenever the contents are stored, so
+/// if the file was deleted or moved away in the meantime storing fails with
+/// [`Error::NotFound`] instead of overwriting whatever took its place. [`File`]s that have the
+/// old contents open keep reading them, new contents are written elsewhere while they are open.
+pub struct FileMut<'pk2, B: Read + Write + Seek = fs::File> {
+    archive: &'pk2 Pk2<B>,
+    /// Components of the absolute path of the file.
+    path: Vec<String>,
+    entry: PackEntry,
+    data: Cursor<Vec<u8>>,
+    dirty: bool,
+}
+
+impl<'pk2, B: Read + Write + Seek> FileMut<'pk2, B> {
+    pub(crate) fn new(archive: &'pk2 Pk2<B>, path: Vec<String>, mut entry: PackEntry) -> Self {
+        // the file is truncated even if nothing gets written to it
+        entry.modify_time = FileTime::now();
+        FileMut { archive, path, entry, data: Cursor::new(Vec::new()), dirty: true }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.data.get_ref().len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn name(&self) -> &str {
+        &self.entry.name
+    }
+
+    /// Sets the creation and last write time of this file, otherwise the last write time is
+    /// updated when the file is stored.
+    pub fn set_file_time(&mut self, creation_time: FileTime, last_write_time: FileTime) {
+        self.entry.create_time = creation_time;
+        self.entry.modify_time = last_write_time;
+        self.dirty = true;
+    }
+
+    fn store(&mut self) -> Result<()> {
+        let data = self.data.get_ref();
+        self.entry = self.archive.store_file(
+            &self.path,
+            data.as_slice(),
+            data.len() as u64,
+            self.entry.create_time,
+            self.entry.modify_time,
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl<B: Read + Write + Seek> Write for FileMut<'_, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.dirty {
+            self.entry.modify_time = FileTime::now();
+            self.dirty = true;
+        }
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.store()?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Read + Write + Seek> Seek for FileMut<'_, B> {
+    /// Seeking beyond the largest size an entry can hold fails, since nothing could be written
+    /// there anyway.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.data.position().checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+        };
+        match new_pos {
+            Some(new_pos) if new_pos <= u64::from(u32::MAX) => {
+                self.data.set_position(new_pos);
+                Ok(new_pos)
+            }
+            Some(new_pos) => Err(Error::FileTooLarge(new_pos).into()),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<B: Read + Write + Seek> Drop for FileMut<'_, B> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+/// The regions of a container that are no longer referenced by any entry, sorted and merged.
+///
+/// Only space released through the archive handle that owns the list is known, the format has
+/// no place to store it, so it starts out empty whenever a container is opened.
+///
+/// Payloads that are still read through open handles are tracked as well. Space released while
+/// it overlaps one of them is held back until the last of those handles is closed, so a reader
+/// never sees the data of another file that took its place.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FreeList {
+    regions: Vec<Range<u64>>,
+    /// Payloads of open handles and the number of handles reading each of them.
+    open: Vec<(Range<u64>, usize)>,
+    /// Released regions that overlap an open payload.
+    pending: Vec<Range<u64>>,
+}
+
+impl FreeList {
+    pub(crate) fn regions(&self) -> &[Range<u64>] {
+        &self.regions
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.regions.iter().map(|region| region.end - region.start).sum()
+    }
+
+    /// Adds a region, merging it with the regions it touches. Regions that are still read through
+    /// an open handle are only added once it is closed.
+    pub(crate) fn release(&mut self, region: Range<u64>) {
+        if region.is_empty() {
+            return;
+        }
+        if self.is_open(&region) {
+            self.pending.push(region);
+            return;
+        }
+        let start = self.regions.partition_point(|r| r.end < region.start);
+        let end = self.regions.partition_point(|r| r.start <= region.end);
+        let touched = &self.regions[start..end];
+        let merged = match (touched.first(), touched.last()) {
+            (Some(first), Some(last)) => first.start.min(region.start)..last.end.max(region.end),
+            _ => region,
+        };
+        self.regions.splice(start..end, [merged]);
+    }
+
+    /// Takes `len` bytes from the first region that is large enough and returns their offset.
+    pub(crate) fn allocate(&mut self, len: u64) -> Option<u64> {
+        if len == 0 {
+            return None;
+        }
+        let idx = self.regions.iter().position(|r| r.end - r.start >= len)?;
+        let region = &mut self.regions[idx];
+        let offset = region.start;
+        region.start += len;
+        if region.is_empty() {
+            self.regions.remove(idx);
+        }
+        Some(offset)
+    }
+
+    /// Returns true if the region overlaps a payload that is read through an open handle.
+    pub(crate) fn is_open(&self, region: &Range<u64>) -> bool {
+        self.open.iter().any(|(open, _)| open.start < region.end && region.start < open.end)
+    }
+
+    /// Registers a handle reading the given payload.
+    pub(crate) fn open(&mut self, payload: Range<u64>) {
+        if payload.is_empty() {
+            return;
+        }
+        match self.open.iter_mut().find(|(open, _)| *open == payload) {
+            Some((_, handles)) => *handles += 1,
+            None => self.open.push((payload, 1)),
+        }
+    }
+
+    /// Unregisters a handle, releasing the pending regions that are no longer read.
+    pub(crate) fn close(&mut self, payload: Range<u64>) {
+        let Some(idx) = self.open.iter().position(|(open, _)| *open == payload) else {
+            return;
+        };
+        self.open[idx].1 -= 1;
+        if self.open[idx].1 == 0 {
+            self.open.swap_remove(idx);
+            for region in std::mem::take(&mut self.pending) {
+                self.release(region);
+            }
+        }
+    }
+}
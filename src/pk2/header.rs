@@ -0,0 +1,120 @@
+use std::io::{self, Read, Write};
+
+use crate::error::{Error, Result};
+use crate::pk2::blowfish::Blowfish;
+
+/// Size of the header at the start of every container.
+pub const HEADER_SIZE: usize = 256;
+
+/// The signature every container starts with, padded with zeros to 30 bytes in the header.
+pub const SIGNATURE: &[u8; 21] = b"JoyMax File Manager!\n";
+
+/// The format version written by the official tools.
+pub const VERSION: u32 = 0x0100_0002;
+
+/// The 256 byte header at the start of every pk2 container.
+///
+/// | offset | size | field     |
+/// |--------|------|-----------|
+/// | 0      | 30   | signature |
+/// | 30     | 4    | version   |
+/// | 34     | 1    | encrypted |
+/// | 35     | 16   | checksum  |
+/// | 51     | 205  | reserved  |
+///
+/// Reading a header only validates the signature, so it can be used to check whether a file is a
+/// container at all. Whether a password is correct is checked with [`Pk2Header::check_password`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pk2Header {
+    pub signature: [u8; 30],
+    pub version: u32,
+    /// Whether the entry blocks are encrypted, the payloads of files never are.
+    pub encrypted: bool,
+    /// The first three bytes of the encrypted [`CHECKSUM_PLAINTEXT`], the rest is zero.
+    ///
+    /// [`CHECKSUM_PLAINTEXT`]: crate::pk2::blowfish::CHECKSUM_PLAINTEXT
+    pub checksum: [u8; 16],
+    pub reserved: [u8; 205],
+}
+
+impl Pk2Header {
+    /// Creates the header for a new container encrypted with the given key.
+    pub fn new(blowfish: &Blowfish) -> Self {
+        Pk2Header { encrypted: true, checksum: blowfish.checksum(), ..Pk2Header::unencrypted() }
+    }
+
+    /// Creates the header for a new container whose entry blocks are stored in plain text.
+    pub fn unencrypted() -> Self {
+        let mut signature = [0; 30];
+        signature[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        Pk2Header {
+            signature,
+            version: VERSION,
+            encrypted: false,
+            checksum: [0; 16],
+            reserved: [0; 205],
+        }
+    }
+
+    /// Parses a header, fails with [`Error::InvalidSignature`] if the signature does not match.
+    pub fn parse(buf: &[u8; HEADER_SIZE]) -> Result<Self> {
+        if !buf.starts_with(SIGNATURE) {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(Pk2Header {
+            signature: buf[..30].try_into().unwrap(),
+            version: u32::from_le_bytes(buf[30..34].try_into().unwrap()),
+            encrypted: buf[34] != 0,
+            checksum: buf[35..51].try_into().unwrap(),
+            reserved: buf[51..].try_into().unwrap(),
+        })
+    }
+
+    /// Serializes the header into its 256 byte representation.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0; HEADER_SIZE];
+        buf[..30].copy_from_slice(&self.signature);
+        buf[30..34].copy_from_slice(&self.version.to_le_bytes());
+        buf[34] = self.encrypted as u8;
+        buf[35..51].copy_from_slice(&self.checksum);
+        buf[51..].copy_from_slice(&self.reserved);
+        buf
+    }
+
+    /// Reads the header from the current position of the reader, which is usually the start of
+    /// a container. Files that are too short to contain a header are reported as
+    /// [`Error::InvalidSignature`].
+    pub fn read_from<R: Read>(mut r: R) -> Result<Self> {
+        let mut buf = [0; HEADER_SIZE];
+        r.read_exact(&mut buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::InvalidSignature,
+            _ => Error::Io(e),
+        })?;
+        Pk2Header::parse(&buf)
+    }
+
+    pub fn write_to<W: Write>(&self, mut w: W) -> Result<()> {
+        w.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Checks whether the blowfish key reproduces the checksum stored in this header.
+    pub fn verify(&self, blowfish: &Blowfish) -> bool {
+        blowfish.verify_checksum(&self.checksum)
+    }
+
+    /// Checks the password against the checksum, returning the cipher for the entry blocks.
+    ///
+    /// Fails with [`Error::WrongPassword`] if the checksum does not match. Unencrypted
+    /// containers accept any password and return `None`.
+    pub fn check_password(&self, password: &str) -> Result<Option<Blowfish>> {
+        if !self.encrypted {
+            return Ok(None);
+        }
+        let blowfish = Blowfish::from_password(password)?;
+        if !self.verify(&blowfish) {
+            return Err(Error::WrongPassword);
+        }
+        Ok(Some(blowfish))
+    }
+}
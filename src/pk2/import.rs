@@ -0,0 +1,343 @@
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::{check_name, join};
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, FileTime, Pk2};
+
+/// What an [`ImportStep`] does to the archive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImportAction {
+    CreateDirectory,
+    /// The file does not exist in the archive yet.
+    Create,
+    /// The file exists in the archive with different contents.
+    Replace,
+    /// The file exists in the archive with the same contents and is left alone. With
+    /// [`Import::trust_mtime`] enabled, a file with the same size and modification time is
+    /// assumed to have the same contents without comparing them.
+    Unchanged,
+}
+
+/// A directory or file of an [`ImportPlan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportStep {
+    /// Path on the host.
+    pub source: PathBuf,
+    /// Path in the archive.
+    pub target: String,
+    pub action: ImportAction,
+    /// Size of the file in bytes, zero for directories.
+    pub size: u64,
+    pub create_time: FileTime,
+    pub modify_time: FileTime,
+}
+
+/// The changes an [`Import`] makes to the archive, in the order they are applied. Parent
+/// directories always come before their children.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportPlan {
+    pub steps: Vec<ImportStep>,
+}
+
+impl ImportPlan {
+    /// Returns the steps with the given action.
+    pub fn filter(&self, action: ImportAction) -> impl Iterator<Item = &ImportStep> {
+        self.steps.iter().filter(move |step| step.action == action)
+    }
+
+    /// Returns the number of bytes that are written to the archive.
+    pub fn bytes(&self) -> u64 {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.action, ImportAction::Create | ImportAction::Replace))
+            .map(|step| step.size)
+            .sum()
+    }
+}
+
+/// Passed to the progress callback of [`Import`] after every step.
+#[derive(Debug)]
+pub struct ImportProgress<'a> {
+    pub step: &'a ImportStep,
+    /// Number of steps handled so far, including this one.
+    pub steps: usize,
+    pub total_steps: usize,
+    /// Size of the files written so far.
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl<B: Read + Write + Seek> Pk2<B> {
+    /// Returns an [`Import`] that copies the host file or directory `source` to the path
+    /// `target` in the archive, further paths can be added with [`Import::add`].
+    pub fn import<P: AsRef<Path>>(&self, source: P, target: &str) -> Import<'_, B> {
+        Import::new(self).add(source, target)
+    }
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&ImportProgress<'_>) + 'a>;
+
+/// Copies files and directory trees from the host filesystem into a container, created with
+/// [`Pk2::import`].
+///
+/// The host paths are first compared with the archive to compute an [`ImportPlan`]. Files that
+/// exist in the archive with the same size and contents are left unchanged, so importing a
+/// patched client directory only rewrites what actually changed. The creation and modification
+/// times of the host files are stored in the entries unless disabled with
+/// [`Import::preserve_times`].
+///
+/// Containers store no checksums, so instead of hashing, which would read both sides anyway,
+/// the contents are compared byte by byte and only when the sizes match. Files are streamed
+/// into the container in chunks rather than buffered in memory.
+///
+/// ```no_run
+/// use gfxfilemanager::pk2::{ImportAction, Pk2};
+///
+/// let archive = Pk2::create("Media.pk2", "169841")?;
+/// let plan = archive.import("client/Media", "/").dry_run(true).run()?;
+/// for step in plan.filter(ImportAction::Replace) {
+///     println!("would replace {}", step.target);
+/// }
+/// # Ok::<(), gfxfilemanager::Error>(())
+/// ```
+pub struct Import<'a, B: Read + Write + Seek> {
+    archive: &'a Pk2<B>,
+    /// Host paths and the archive paths they are copied to.
+    mappings: Vec<(PathBuf, String)>,
+    preserve_times: bool,
+    trust_mtime: bool,
+    dry_run: bool,
+    progress: Option<ProgressFn<'a>>,
+}
+
+impl<'a, B: Read + Write + Seek> Import<'a, B> {
+    pub(crate) fn new(archive: &'a Pk2<B>) -> Self {
+        Import {
+            archive,
+            mappings: Vec::new(),
+            preserve_times: true,
+            trust_mtime: false,
+            dry_run: false,
+            progress: None,
+        }
+    }
+
+    /// Adds another host file or directory, which is copied to the path `target` in the
+    /// archive. The children of a directory end up directly in `target`.
+    pub fn add<P: AsRef<Path>>(mut self, source: P, target: &str) -> Self {
+        self.mappings.push((source.as_ref().to_owned(), target.to_owned()));
+        self
+    }
+
+    /// Whether the creation and modification times of the host files are stored in the
+    /// entries. Enabled by default, otherwise the entries get the current time.
+    pub fn preserve_times(mut self, preserve: bool) -> Self {
+        self.preserve_times = preserve;
+        self
+    }
+
+    /// Whether files whose size and modification time match the entry are assumed to be
+    /// unchanged without comparing their contents, like `rsync` does. Disabled by default, since
+    /// a file rewritten with the same size and its old modification time restored would not be
+    /// picked up.
+    pub fn trust_mtime(mut self, trust: bool) -> Self {
+        self.trust_mtime = trust;
+        self
+    }
+
+    /// Only computes the plan and reports its progress, without modifying the archive.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Calls the closure after every step of the plan.
+    pub fn progress<P>(mut self, progress: P) -> Self
+    where
+        P: FnMut(&ImportProgress<'_>) + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Compares the host paths with the archive and returns the changes an import would make.
+    pub fn plan(&self) -> Result<ImportPlan> {
+        let mut plan = ImportPlan::default();
+        for (source, target) in &self.mappings {
+            let metadata = fs::metadata(source)?;
+            let exists = match self.archive.lookup(target) {
+                Ok(entry) => Some(entry.kind),
+                Err(Error::NotFound(_)) => None,
+                Err(e) => return Err(e),
+            };
+            if metadata.is_dir() {
+                self.plan_directory(&mut plan, source, target, &metadata, exists)?;
+            } else {
+                self.plan_file(&mut plan, source, target, &metadata, exists)?;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Computes the plan and applies it, unless this is a dry run. Errors abort the import,
+    /// the steps applied until then are kept.
+    pub fn run(mut self) -> Result<ImportPlan> {
+        let plan = self.plan()?;
+        let total_steps = plan.steps.len();
+        let total_bytes = plan.bytes();
+        let mut bytes = 0;
+        for (idx, step) in plan.steps.iter().enumerate() {
+            match step.action {
+                ImportAction::CreateDirectory => {
+                    if !self.dry_run {
+                        self.archive.create_directory(&step.target)?;
+                    }
+                }
+                ImportAction::Create | ImportAction::Replace => {
+                    if !self.dry_run {
+                        self.write_file(step)?;
+                    }
+                    bytes += step.size;
+                }
+                ImportAction::Unchanged => {}
+            }
+            if let Some(progress) = &mut self.progress {
+                progress(&ImportProgress { step, steps: idx + 1, total_steps, bytes, total_bytes });
+            }
+        }
+        Ok(plan)
+    }
+
+    fn plan_directory(
+        &self,
+        plan: &mut ImportPlan,
+        source: &Path,
+        target: &str,
+        metadata: &fs::Metadata,
+        exists: Option<EntryKind>,
+    ) -> Result<()> {
+        match exists {
+            Some(EntryKind::Directory) => {}
+            Some(_) => return Err(Error::AlreadyExists(target.to_owned())),
+            None => plan.steps.push(step(source, target, ImportAction::CreateDirectory, metadata)),
+        }
+        let mut children = fs::read_dir(source)?
+            .map(|entry| {
+                let entry = entry?;
+                let name = entry
+                    .file_name()
+                    .into_string()
+                    .map_err(|name| Error::InvalidPath(name.to_string_lossy().into_owned()))?;
+                check_name(&name)?;
+                Ok((name, entry.path()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        children.sort();
+
+        // the contents of a directory that does not exist yet are not looked up
+        let existing = match exists {
+            Some(_) => self
+                .archive
+                .read_dir(target)?
+                .map(|entry| entry.map(|entry| (entry.name.to_ascii_lowercase(), entry.kind)))
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        for (name, path) in children {
+            let target = join(target, &name);
+            let metadata = fs::metadata(&path)?;
+            let exists = existing
+                .iter()
+                .find(|(existing, _)| existing.eq_ignore_ascii_case(&name))
+                .map(|&(_, kind)| kind);
+            if metadata.is_dir() {
+                self.plan_directory(plan, &path, &target, &metadata, exists)?;
+            } else {
+                self.plan_file(plan, &path, &target, &metadata, exists)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn plan_file(
+        &self,
+        plan: &mut ImportPlan,
+        source: &Path,
+        target: &str,
+        metadata: &fs::Metadata,
+        exists: Option<EntryKind>,
+    ) -> Result<()> {
+        if u32::try_from(metadata.len()).is_err() {
+            return Err(Error::FileTooLarge(metadata.len()));
+        }
+        let mut step = step(source, target, ImportAction::Create, metadata);
+        step.action = match exists {
+            None => ImportAction::Create,
+            Some(EntryKind::File) => {
+                let file = self.archive.open_file(target)?;
+                let same_time = self.trust_mtime
+                    && metadata.modified().is_ok()
+                    && file.file_time().1 == step.modify_time;
+                if file.len() == metadata.len()
+                    && (same_time || same_contents(file, fs::File::open(source)?)?)
+                {
+                    ImportAction::Unchanged
+                } else {
+                    ImportAction::Replace
+                }
+            }
+            Some(_) => return Err(Error::AlreadyExists(target.to_owned())),
+        };
+        plan.steps.push(step);
+        Ok(())
+    }
+
+    /// Streams the host file into the archive in chunks, so large files are not held in memory.
+    fn write_file(&self, step: &ImportStep) -> Result<()> {
+        let src = fs::File::open(&step.source)?;
+        let len = src.metadata()?.len();
+        let (path, entry) = self.archive.create_entry(&step.target)?;
+        let (create_time, modify_time) = if self.preserve_times {
+            (step.create_time, step.modify_time)
+        } else {
+            (entry.create_time, FileTime::now())
+        };
+        self.archive.store_file(&path, src, len, create_time, modify_time)?;
+        Ok(())
+    }
+}
+
+fn step(source: &Path, target: &str, action: ImportAction, metadata: &fs::Metadata) -> ImportStep {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    ImportStep {
+        source: source.to_owned(),
+        target: target.to_owned(),
+        action,
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        create_time: FileTime::from(metadata.created().unwrap_or(modified)),
+        modify_time: FileTime::from(modified),
+    }
+}
+
+/// Compares two streams of the same length chunk by chunk.
+fn same_contents(mut a: impl Read, mut b: impl Read) -> Result<bool> {
+    let mut buf_a = vec![0; 64 * 1024];
+    let mut buf_b = vec![0; 64 * 1024];
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        match b.read_exact(&mut buf_b[..n]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
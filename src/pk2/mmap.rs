@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::error::{Error, Result};
+use crate::pk2::{EntryKind, Pk2};
+
+/// A read-only stream over a memory-mapped container, used as the backing of a [`MappedPk2`].
+///
+/// Writing to it always fails, so the archive can be browsed through [`FileManager`] like any
+/// other, but modifications return an error.
+///
+/// [`FileManager`]: crate::FileManager
+#[derive(Clone)]
+pub struct MmapStream {
+    map: Arc<Mmap>,
+    pos: u64,
+}
+
+impl Read for MmapStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data =
+            usize::try_from(self.pos).ok().and_then(|pos| self.map.get(pos..)).unwrap_or(&[]);
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for MmapStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => (self.map.len() as u64).checked_add_signed(offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl Write for MmapStream {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "memory-mapped archives are read-only"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A container that is memory-mapped instead of read through a file handle.
+///
+/// [`MappedPk2::bytes`] returns the payload of a file as a slice of the mapping, so even
+/// multi-gigabyte containers can be read without copying their data. Everything else, like
+/// lookups or [`Pk2::read_dir`], is available through [`Deref`] to the underlying [`Pk2`].
+///
+/// The mapping reflects the file on disk, so the container must not be modified by another
+/// process while it is open, which would change or invalidate the returned slices.
+pub struct MappedPk2 {
+    map: Arc<Mmap>,
+    archive: Pk2<MmapStream>,
+}
+
+impl MappedPk2 {
+    /// Memory-maps an existing container
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path of the container on the host filesystem
+    /// * password - Password required for accessing the container
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the mapping is only ever read, modifying the file while it is mapped is
+        // documented as unsupported above.
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        let archive = Pk2::open_in(MmapStream { map: Arc::clone(&map), pos: 0 }, password)?;
+        Ok(MappedPk2 { map, archive })
+    }
+
+    /// Returns the whole container.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Returns the contents of a file without copying them.
+    pub fn bytes(&self, path: &str) -> Result<&[u8]> {
+        let entry = self.archive.lookup(path)?;
+        if entry.kind != EntryKind::File {
+            return Err(Error::NotAFile(path.to_owned()));
+        }
+        usize::try_from(entry.position)
+            .ok()
+            .and_then(|start| self.map.get(start..start.checked_add(entry.size as usize)?))
+            .ok_or(Error::CorruptBlock {
+                offset: entry.position,
+                reason: "file data extends past the end of the container",
+            })
+    }
+}
+
+impl Deref for MappedPk2 {
+    type Target = Pk2<MmapStream>;
+
+    fn deref(&self) -> &Pk2<MmapStream> {
+        &self.archive
+    }
+}
+
+impl DerefMut for MappedPk2 {
+    fn deref_mut(&mut self) -> &mut Pk2<MmapStream> {
+        &mut self.archive
+    }
+}
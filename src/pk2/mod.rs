@@ -0,0 +1,417 @@
+//! A native implementation of the pk2 container format that does not depend on
+//! GFXFileManager.dll and therefore works on every target.
+//!
+//! A container starts with a 256 byte header followed by the root directory's entry block.
+//! Every directory is a chain of blocks of 20 entries each, which are encrypted with blowfish
+//! when the container is password protected. File payloads are stored unencrypted anywhere
+//! after the header.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::backend::{self, filter_entries, split_pattern, ArchivePath, DirEntry, FileManager};
+use crate::error::{Error, Result};
+
+#[cfg(feature = "tokio")]
+mod async_pk2;
+pub mod blowfish;
+mod compact;
+mod entry;
+mod file;
+mod free;
+mod header;
+mod import;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod read_dir;
+pub mod recover;
+mod repair;
+mod verify;
+mod write;
+
+#[cfg(feature = "tokio")]
+pub use self::async_pk2::{AsyncFile, AsyncPk2};
+pub use self::compact::CompactSummary;
+pub use self::entry::{EntryKind, FileTime, MAX_NAME_LEN};
+pub use self::file::{File, FileMut};
+pub use self::header::{Pk2Header, HEADER_SIZE};
+pub use self::import::{Import, ImportAction, ImportPlan, ImportProgress, ImportStep};
+#[cfg(feature = "mmap")]
+pub use self::mmap::{MappedPk2, MmapStream};
+pub use self::read_dir::ReadDir;
+pub use self::repair::{RepairSummary, LOST_AND_FOUND};
+pub use self::verify::{Problem, ProblemKind, VerifyReport};
+
+use self::blowfish::Blowfish;
+use self::entry::{PackBlock, PackEntry, BLOCK_SIZE};
+use self::free::FreeList;
+use self::write::payload;
+
+/// The password used by the official Silkroad Online client.
+pub const DEFAULT_PASSWORD: &str = "169841";
+
+/// Offset of the root directory's first block.
+pub(crate) const ROOT_BLOCK: u64 = HEADER_SIZE as u64;
+
+/// A pk2 container opened without the dll.
+///
+/// The archive is `Send + Sync` as long as its stream is `Send`, so it can be shared between
+/// threads which each open their own [`File`]s. Every file keeps its own position and reads its
+/// data at absolute offsets, so the threads do not disturb each other. Containers opened from a
+/// path with [`Pk2::open`] or [`Pk2::create`] are read with positional reads on a second handle
+/// of the file, so readers do not wait for each other or for writers. Other streams are read
+/// under a lock.
+pub struct Pk2<B = fs::File> {
+    stream: Mutex<B>,
+    /// A handle of the container file that is only used for positional reads.
+    reader: Option<fs::File>,
+    /// Held by operations that modify entries, so that concurrent modifications do not overwrite
+    /// each other's blocks.
+    edits: Mutex<()>,
+    /// Space released by deleted or moved payloads and blocks, reused for new ones.
+    free: Mutex<FreeList>,
+    blowfish: Option<Blowfish>,
+    /// Components of the current working directory.
+    cwd: ArchivePath,
+}
+
+impl Pk2<fs::File> {
+    /// Opens an existing container
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path of the container on the host filesystem
+    /// * password - Password required for accessing the container
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        let archive = Self::open_in(file.try_clone()?, password)?;
+        Ok(Pk2 { reader: Some(file), ..archive })
+    }
+}
+
+impl<B> Pk2<B> {
+    /// Locks the free list, which is also used when dropping handles, so it needs no bounds.
+    pub(crate) fn free_list(&self) -> MutexGuard<'_, FreeList> {
+        self.free.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<B: Read + Seek> Pk2<B> {
+    /// Opens a container stored in the given stream, for example an in-memory buffer.
+    pub fn open_in(mut stream: B, password: &str) -> Result<Self> {
+        stream.seek(SeekFrom::Start(0))?;
+        let blowfish = Pk2Header::read_from(&mut stream)?.check_password(password)?;
+        Ok(Pk2::new(stream, blowfish))
+    }
+
+    pub(crate) fn new(stream: B, blowfish: Option<Blowfish>) -> Self {
+        Pk2 {
+            stream: Mutex::new(stream),
+            reader: None,
+            edits: Mutex::new(()),
+            free: Mutex::new(FreeList::default()),
+            blowfish,
+            cwd: ArchivePath::root(),
+        }
+    }
+
+    /// Consumes the archive, returning the underlying stream.
+    pub fn into_inner(self) -> B {
+        self.stream.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the stream, a panic while it was locked cannot leave it in an invalid state since
+    /// every access seeks first.
+    pub(crate) fn stream(&self) -> MutexGuard<'_, B> {
+        self.stream.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn lock_edits(&self) -> MutexGuard<'_, ()> {
+        self.edits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the regions of the container that were released by deleting, moving or
+    /// replacing entries through this handle, sorted by offset. They are reused for new
+    /// payloads and blocks before the container grows. Space that was already unused when the
+    /// container was opened is not known, since the format does not record it.
+    ///
+    /// Space that is still read through an open [`File`] is only listed once the file is
+    /// dropped, so open files keep reading their own data even if they were deleted or replaced.
+    pub fn free_regions(&self) -> Vec<Range<u64>> {
+        self.free_list().regions().to_vec()
+    }
+
+    /// Returns the total size of the [free regions](Pk2::free_regions) in bytes.
+    pub fn free_bytes(&self) -> u64 {
+        self.free_list().total()
+    }
+
+    /// Opens a file inside the container and returns a File object
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path relative to the current directory or absolute path inside the archive,
+    ///   both `/` and `\` are accepted as separators
+    pub fn open_file(&self, path: &str) -> Result<File<'_, B>> {
+        Ok(File::new(self, self.open_entry(path)?))
+    }
+
+    /// Looks up a file and registers a handle reading its payload, which must be closed through
+    /// the free list again. The edit lock keeps the payload from being released in between.
+    pub(crate) fn open_entry(&self, path: &str) -> Result<PackEntry> {
+        let _edit = self.lock_edits();
+        let entry = self.lookup(path)?;
+        if entry.kind != EntryKind::File {
+            return Err(Error::NotAFile(path.to_owned()));
+        }
+        self.free_list().open(payload(&entry));
+        Ok(entry)
+    }
+
+    /// Opens an entry that was not looked up by path, for example one found by scanning.
+    pub(crate) fn open_payload(&self, entry: PackEntry) -> File<'_, B> {
+        self.free_list().open(payload(&entry));
+        File::new(self, entry)
+    }
+
+    /// Reads the whole file at the given path.
+    pub fn bytes(&self, path: &str) -> Result<Vec<u8>> {
+        let mut file = self.open_file(path)?;
+        let mut buf = Vec::with_capacity(usize::try_from(file.len()).unwrap_or(0));
+        file.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Changes the current working directory
+    pub fn change_directory(&mut self, path: &str) -> Result<()> {
+        let cwd = self.cwd.resolve(path)?;
+        match self.lookup_components(cwd.components())?.kind {
+            EntryKind::Directory => {
+                self.cwd = cwd;
+                Ok(())
+            }
+            _ => Err(Error::NotADirectory(path.to_owned())),
+        }
+    }
+
+    /// Resets the current working directory to the root
+    pub fn reset_directory(&mut self) {
+        self.cwd = ArchivePath::root();
+    }
+
+    /// Returns the path of the current working directory, which is empty for the root
+    pub fn current_directory(&self) -> String {
+        self.cwd.to_string()
+    }
+
+    /// Returns true if a file or directory exists at the given path
+    pub fn file_exists(&self, path: &str) -> Result<bool> {
+        match self.lookup(path) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_) | Error::NotADirectory(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns an iterator over the entries of a directory, the blocks of the directory are read
+    /// as the iterator advances. The `.` and `..` entries are never returned.
+    pub fn read_dir(&self, path: &str) -> Result<ReadDir<'_, B>> {
+        let dir = self.lookup(path)?;
+        if dir.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        Ok(ReadDir::new(self, path, dir.position))
+    }
+
+    /// Returns the entries of a directory matching the pattern, whose last component may
+    /// contain `*` and `?` wildcards. The `.` and `..` entries are never returned.
+    pub fn find_files(&self, pattern: &str) -> Result<Vec<DirEntry>> {
+        let (dir, name_pattern) = split_pattern(pattern);
+        filter_entries(self.read_dir(dir)?, name_pattern)
+    }
+
+    /// Resolves a path against the current directory into its normalized components.
+    pub(crate) fn resolve(&self, path: &str) -> Result<Vec<String>> {
+        backend::resolve(&self.cwd, path)
+    }
+
+    /// Looks up a path relative to the current directory, the lookup is case-insensitive.
+    pub(crate) fn lookup(&self, path: &str) -> Result<PackEntry> {
+        self.lookup_components(&self.resolve(path)?)
+    }
+
+    /// Looks up the entry at the given components starting at the root directory.
+    pub(crate) fn lookup_components(&self, components: &[String]) -> Result<PackEntry> {
+        let mut entry = root_entry();
+        for (depth, component) in components.iter().enumerate() {
+            if entry.kind != EntryKind::Directory {
+                return Err(Error::NotADirectory(components[..depth].join("/")));
+            }
+            entry = match self.find_in_directory(entry.position, component)? {
+                Some((block, idx)) => block.entries[idx].clone(),
+                None => return Err(Error::NotFound(components.join("/"))),
+            };
+        }
+        Ok(entry)
+    }
+
+    /// Searches the directory whose first block is at `dir` for an entry called `name`, returning
+    /// the block that contains it together with the entry's index in that block.
+    pub(crate) fn find_in_directory(
+        &self,
+        dir: u64,
+        name: &str,
+    ) -> Result<Option<(PackBlock, usize)>> {
+        for block in self.read_chain(dir)? {
+            if let Some(idx) = block.entries.iter().position(|e| e.is_named(name)) {
+                return Ok(Some((block, idx)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads all blocks of the directory whose first block is at `offset`.
+    pub(crate) fn read_chain(&self, offset: u64) -> Result<Vec<PackBlock>> {
+        let mut blocks = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(offset);
+        while let Some(offset) = next {
+            if !visited.insert(offset) {
+                return Err(Error::CorruptBlock { offset, reason: "cycle in block chain" });
+            }
+            let block = self.read_block(offset)?;
+            next = block.next_block();
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+
+    pub(crate) fn read_block(&self, offset: u64) -> Result<PackBlock> {
+        PackBlock::parse(offset, self.read_raw_block(offset)?)
+    }
+
+    /// Reads and decrypts a block without parsing it, for checking damaged containers.
+    pub(crate) fn read_raw_block(&self, offset: u64) -> Result<[u8; BLOCK_SIZE]> {
+        let mut buf = [0; BLOCK_SIZE];
+        self.read_at(offset, &mut buf)?;
+        if let Some(blowfish) = &self.blowfish {
+            blowfish.decrypt(&mut buf)?;
+        }
+        Ok(buf)
+    }
+
+    pub(crate) fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        if let Some(result) = self.reader.as_ref().and_then(|file| read_exact_at(file, buf, offset))
+        {
+            return Ok(result?);
+        }
+        let mut stream = self.stream();
+        stream.seek(SeekFrom::Start(offset))?;
+        stream.read_exact(buf)?;
+        Ok(())
+    }
+}
+
+/// Fills the buffer from the given offset of the file without using its cursor. Returns `None`
+/// on targets without positional reads.
+///
+/// On windows the cursor of the file is moved anyway, which is harmless since every access
+/// through the locked stream seeks first.
+fn read_exact_at(file: &fs::File, buf: &mut [u8], offset: u64) -> Option<io::Result<()>> {
+    #[cfg(unix)]
+    {
+        Some(std::os::unix::fs::FileExt::read_exact_at(file, buf, offset))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileExt;
+
+        let (mut buf, mut offset) = (buf, offset);
+        while !buf.is_empty() {
+            match file.seek_read(buf, offset) {
+                Ok(0) => return Some(Err(io::ErrorKind::UnexpectedEof.into())),
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok(()))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (file, buf, offset);
+        None
+    }
+}
+
+/// The root directory has no entry of its own, so we make one up to start lookups from.
+fn root_entry() -> PackEntry {
+    PackEntry { kind: EntryKind::Directory, position: ROOT_BLOCK, ..PackEntry::empty() }
+}
+
+impl<B: Read + Write + Seek> FileManager for Pk2<B> {
+    type File<'a>
+        = File<'a, B>
+    where
+        Self: 'a;
+    type FileMut<'a>
+        = FileMut<'a, B>
+    where
+        Self: 'a;
+    type ReadDir<'a>
+        = ReadDir<'a, B>
+    where
+        Self: 'a;
+
+    fn open_file(&self, path: &str) -> Result<File<'_, B>> {
+        Pk2::open_file(self, path)
+    }
+
+    fn create_file(&self, path: &str) -> Result<FileMut<'_, B>> {
+        Pk2::create_file(self, path)
+    }
+
+    fn delete_file(&self, path: &str) -> Result<()> {
+        Pk2::delete_file(self, path)
+    }
+
+    fn create_directory(&self, path: &str) -> Result<()> {
+        Pk2::create_directory(self, path)
+    }
+
+    fn delete_directory(&self, path: &str) -> Result<()> {
+        Pk2::delete_directory(self, path)
+    }
+
+    fn bytes(&self, path: &str) -> Result<Vec<u8>> {
+        Pk2::bytes(self, path)
+    }
+
+    fn change_directory(&mut self, path: &str) -> Result<()> {
+        Pk2::change_directory(self, path)
+    }
+
+    fn reset_directory(&mut self) -> Result<()> {
+        Pk2::reset_directory(self);
+        Ok(())
+    }
+
+    fn current_directory(&self) -> Result<String> {
+        Ok(Pk2::current_directory(self))
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool> {
+        Pk2::file_exists(self, path)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<ReadDir<'_, B>> {
+        Pk2::read_dir(self, path)
+    }
+}
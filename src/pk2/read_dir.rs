@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Seek};
+
+use crate::backend::{join, DirEntry};
+use crate::error::{Error, Result};
+use crate::pk2::entry::{EntryKind, PackBlock, PackEntry};
+use crate::pk2::Pk2;
+
+/// Iterator over the entries of a directory, returned by [`Pk2::read_dir`].
+///
+/// Blocks are read one at a time as the iterator advances, an error reading the next block is
+/// returned once and ends the iteration.
+pub struct ReadDir<'pk2, B = fs::File> {
+    archive: &'pk2 Pk2<B>,
+    path: String,
+    next_block: Option<u64>,
+    block: Option<PackBlock>,
+    idx: usize,
+    /// Offsets of the blocks that were read, to detect cycles in the chain.
+    visited: HashSet<u64>,
+}
+
+impl<'pk2, B: Read + Seek> ReadDir<'pk2, B> {
+    pub(crate) fn new(archive: &'pk2 Pk2<B>, path: &str, first_block: u64) -> Self {
+        ReadDir {
+            archive,
+            path: path.to_owned(),
+            next_block: Some(first_block),
+            block: None,
+            idx: 0,
+            visited: HashSet::new(),
+        }
+    }
+
+    fn dir_entry(&self, entry: &PackEntry) -> DirEntry {
+        DirEntry {
+            path: join(&self.path, &entry.name),
+            name: entry.name.clone(),
+            kind: entry.kind,
+            size: u64::from(entry.size),
+            create_time: entry.create_time,
+            modify_time: entry.modify_time,
+            access_time: entry.access_time,
+            position: Some(entry.position),
+        }
+    }
+}
+
+impl<B: Read + Seek> Iterator for ReadDir<'_, B> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        loop {
+            if let Some(block) = &self.block {
+                if let Some(entry) = block.entries.get(self.idx) {
+                    self.idx += 1;
+                    if entry.kind != EntryKind::Empty && entry.name != "." && entry.name != ".." {
+                        return Some(Ok(self.dir_entry(entry)));
+                    }
+                    continue;
+                }
+                self.next_block = block.next_block();
+            }
+            let offset = self.next_block.take()?;
+            self.block = None;
+            if !self.visited.insert(offset) {
+                return Some(Err(Error::CorruptBlock { offset, reason: "cycle in block chain" }));
+            }
+            match self.archive.read_block(offset) {
+                Ok(block) => {
+                    self.block = Some(block);
+                    self.idx = 0;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Offline recovery of lost container passwords.
+//!
+//! The header only stores three bytes of the encrypted checksum, so roughly one in 16 million
+//! wrong passwords matches it. Candidates that pass the checksum are therefore confirmed by
+//! decrypting the first entry of the root directory, which has to be its `.` entry.
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::Result;
+use crate::pk2::blowfish::Blowfish;
+use crate::pk2::entry::{EntryKind, PackEntry, ENTRY_SIZE};
+use crate::pk2::header::Pk2Header;
+use crate::pk2::ROOT_BLOCK;
+
+/// Tests candidate passwords against a container without opening it.
+pub struct PasswordRecovery {
+    header: Pk2Header,
+    /// The encrypted first entry of the root directory.
+    root_entry: [u8; ENTRY_SIZE],
+}
+
+impl PasswordRecovery {
+    /// Reads the header and root entry of the container at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(fs::File::open(path)?)
+    }
+
+    /// Reads the header and root entry of a container stored in the given stream.
+    pub fn new<R: Read + Seek>(mut stream: R) -> Result<Self> {
+        stream.seek(SeekFrom::Start(0))?;
+        let header = Pk2Header::read_from(&mut stream)?;
+        let mut root_entry = [0; ENTRY_SIZE];
+        stream.seek(SeekFrom::Start(ROOT_BLOCK))?;
+        stream.read_exact(&mut root_entry)?;
+        Ok(PasswordRecovery { header, root_entry })
+    }
+
+    pub fn header(&self) -> &Pk2Header {
+        &self.header
+    }
+
+    /// Returns true if the password opens the container. Unencrypted containers accept every
+    /// password.
+    pub fn check(&self, password: &str) -> bool {
+        if !self.header.encrypted {
+            return true;
+        }
+        let Ok(blowfish) = Blowfish::from_password(password) else {
+            return false;
+        };
+        if !self.header.verify(&blowfish) {
+            return false;
+        }
+        let mut entry = self.root_entry;
+        blowfish.decrypt(&mut entry).is_ok()
+            && PackEntry::parse(&entry)
+                .is_ok_and(|entry| entry.kind == EntryKind::Directory && entry.name == ".")
+    }
+
+    /// Returns an iterator over the candidates that open the container.
+    pub fn search<'a, I>(&'a self, candidates: I) -> impl Iterator<Item = I::Item> + 'a
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        I::IntoIter: 'a,
+    {
+        candidates.into_iter().filter(|password| self.check(password.as_ref()))
+    }
+}
+
+/// Every password made up of the characters of an alphabet within a range of lengths, shortest
+/// first and in alphabet order within each length.
+///
+/// ```
+/// use gfxfilemanager::pk2::recover::KeySpace;
+///
+/// let passwords: Vec<String> = KeySpace::new("ab", 1, 2).collect();
+/// assert_eq!(passwords, ["a", "b", "aa", "ab", "ba", "bb"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeySpace {
+    alphabet: Vec<char>,
+    max_len: usize,
+    /// Alphabet indices of the next password, `None` once the key-space is exhausted.
+    next: Option<Vec<usize>>,
+}
+
+impl KeySpace {
+    /// Creates the key-space of the given alphabet, duplicate characters are ignored. Empty
+    /// passwords are never produced since they are not valid.
+    pub fn new(alphabet: &str, min_len: usize, max_len: usize) -> Self {
+        let mut chars = Vec::new();
+        for c in alphabet.chars() {
+            if !chars.contains(&c) {
+                chars.push(c);
+            }
+        }
+        let min_len = min_len.max(1);
+        let next = (!chars.is_empty() && min_len <= max_len).then(|| vec![0; min_len]);
+        KeySpace { alphabet: chars, max_len, next }
+    }
+
+    /// Returns the number of passwords that are left, saturating at `u128::MAX`.
+    pub fn remaining(&self) -> u128 {
+        let Some(next) = &self.next else {
+            return 0;
+        };
+        let base = self.alphabet.len() as u128;
+        // position of `next` within the passwords of its length
+        let position =
+            next.iter().fold(0u128, |acc, &i| acc.saturating_mul(base).saturating_add(i as u128));
+        (next.len()..=self.max_len)
+            .map(|len| base.saturating_pow(len as u32))
+            .fold(0u128, u128::saturating_add)
+            .saturating_sub(position)
+    }
+}
+
+impl Iterator for KeySpace {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let indices = self.next.as_mut()?;
+        let password = indices.iter().map(|&i| self.alphabet[i]).collect();
+        // advance like an odometer, growing the password once every position wrapped around
+        let mut pos = indices.len();
+        loop {
+            if pos == 0 {
+                if indices.len() < self.max_len {
+                    indices.iter_mut().for_each(|i| *i = 0);
+                    indices.push(0);
+                } else {
+                    self.next = None;
+                }
+                break;
+            }
+            pos -= 1;
+            indices[pos] += 1;
+            if indices[pos] < self.alphabet.len() {
+                break;
+            }
+            indices[pos] = 0;
+        }
+        Some(password)
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::Result;
+use crate::pk2::entry::{EntryKind, PackEntry, BLOCK_SIZE, ENTRY_SIZE};
+use crate::pk2::verify::invalid_name;
+use crate::pk2::write::directory_block;
+use crate::pk2::{Pk2, HEADER_SIZE, ROOT_BLOCK};
+
+/// Name of the directory that receives the directories whose parent was lost.
+pub const LOST_AND_FOUND: &str = "lost+found";
+
+/// The outcome of [`Pk2::repair_to`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepairSummary {
+    /// Number of entry blocks that were salvaged.
+    pub blocks: usize,
+    pub directories: usize,
+    pub files: usize,
+    /// Directories that could not be reached from the root and were put into
+    /// [`LOST_AND_FOUND`].
+    pub orphans: usize,
+    /// Entries that were dropped because they were invalid, pointed past the end of the
+    /// container or duplicated another entry.
+    pub dropped: usize,
+}
+
+/// A directory found while scanning the container.
+#[derive(Default)]
+struct Found {
+    files: Vec<PackEntry>,
+    /// Entries of the subdirectories, pointing to their first blocks.
+    directories: Vec<PackEntry>,
+}
+
+/// A directory of the repaired container and the files that go into it. The entries keep the
+/// stored bytes of their names, which are written as they are.
+struct Placed {
+    /// Index of the parent directory, `None` for the root.
+    parent: Option<usize>,
+    entry: PackEntry,
+    files: Vec<PackEntry>,
+    /// The stored names of the children in lowercase, to drop duplicates.
+    names: HashSet<Vec<u8>>,
+}
+
+impl<B: Read + Seek> Pk2<B> {
+    /// Writes the entries that can still be read from a damaged container to a new file at
+    /// `path`, which must not exist yet. See [`Pk2::repair_to`].
+    pub fn repair<P: AsRef<Path>>(&self, path: P) -> Result<RepairSummary> {
+        let file = fs::OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        self.repair_to(file)
+    }
+
+    /// Salvages everything that can still be read from a damaged container and writes it to a
+    /// fresh container in `dst`, which gets the same header and password.
+    ///
+    /// Instead of following the tree from the root, the container is scanned for the first
+    /// blocks of directories, which are recognized by their `.` entry pointing back to the block
+    /// itself. Every offset a block would have if the container only held blocks is checked, as
+    /// well as every offset the directory entries and `next_block` links of the blocks found
+    /// point to. Blocks that are neither aligned like that nor linked from anywhere are found by
+    /// searching for the encrypted start of a `.` entry, which only works if its name has no
+    /// leftover bytes after the terminating null byte.
+    ///
+    /// The chains of the directories are followed as far as they lead to readable blocks, and
+    /// invalid entries are skipped one by one. Directories that cannot be reached from the root
+    /// anymore are put into [`LOST_AND_FOUND`] under the hexadecimal offset of their first
+    /// block, since their names were stored in the lost parent. Files whose payload lies past
+    /// the end of the container are dropped. Payloads are copied in chunks, and names are
+    /// written with their stored bytes, so names in other encodings than UTF-8 are kept.
+    ///
+    /// Only the header has to be intact, it holds the checksum of the password.
+    pub fn repair_to<W: Read + Write + Seek>(&self, mut dst: W) -> Result<RepairSummary> {
+        let mut summary = RepairSummary::default();
+        let found = self.scan(&mut summary)?;
+        let placed = place(&found, &mut summary);
+
+        let mut header = [0; HEADER_SIZE];
+        self.read_at(0, &mut header)?;
+        dst.seek(SeekFrom::Start(0))?;
+        dst.write_all(&header)?;
+        let repaired = Pk2::new(dst, self.blowfish.clone());
+        repaired.write_block(&directory_block(ROOT_BLOCK, ROOT_BLOCK))?;
+
+        // the entries are written by the offsets of their directories rather than by path, since
+        // names in other encodings are only unique in their stored form
+        let mut firsts = Vec::with_capacity(placed.len());
+        for dir in &placed {
+            let first = match dir.parent {
+                Some(parent) => repaired.insert_directory(firsts[parent], dir.entry.clone())?,
+                None => ROOT_BLOCK,
+            };
+            firsts.push(first);
+            for entry in &dir.files {
+                repaired.insert_file(first, entry.clone(), self.open_payload(entry.clone()))?;
+                summary.files += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Finds the first blocks of all directories and reads their chains.
+    fn scan(&self, summary: &mut RepairSummary) -> Result<BTreeMap<u64, Found>> {
+        let len = self.stream().seek(SeekFrom::End(0))?;
+        let mut candidates = vec![ROOT_BLOCK];
+
+        // the first slot of every block on the grid the blocks would have without payloads
+        let mut chunk = vec![0; 400 * BLOCK_SIZE];
+        let mut offset = ROOT_BLOCK;
+        while offset + BLOCK_SIZE as u64 <= len {
+            let n = chunk.len().min((len - offset) as usize);
+            self.read_at(offset, &mut chunk[..n])?;
+            for (i, block) in chunk[..n].chunks_exact(BLOCK_SIZE).enumerate() {
+                let first = offset + (i * BLOCK_SIZE) as u64;
+                if self.starts_directory(first, &block[..ENTRY_SIZE])? {
+                    candidates.push(first);
+                }
+            }
+            offset += (n - n % BLOCK_SIZE) as u64;
+        }
+
+        // the start of a `.` entry, which is the first slot of every directory
+        let mut marker = [0; 8];
+        marker[..2].copy_from_slice(&[EntryKind::Directory as u8, b'.']);
+        if let Some(blowfish) = &self.blowfish {
+            blowfish.encrypt(&mut marker)?;
+        }
+        let mut chunk = vec![0; 1024 * 1024];
+        let mut offset = ROOT_BLOCK;
+        while offset + marker.len() as u64 <= len {
+            let n = chunk.len().min((len - offset) as usize);
+            self.read_at(offset, &mut chunk[..n])?;
+            let matches = chunk[..n].windows(marker.len()).enumerate();
+            candidates
+                .extend(matches.filter(|(_, w)| *w == marker).map(|(i, _)| offset + i as u64));
+            // the chunks overlap so markers on their boundary are found
+            offset += (n - marker.len() + 1) as u64;
+        }
+
+        let mut found = BTreeMap::new();
+        for first in candidates {
+            if !found.contains_key(&first) && self.is_first_block(first, len)? {
+                found.insert(first, Found::default());
+            }
+        }
+
+        // blocks that are claimed by one directory are not read again for another
+        let mut claimed: HashSet<u64> = found.keys().copied().collect();
+        let mut pending: Vec<u64> = found.keys().copied().collect();
+        while let Some(first) = pending.pop() {
+            let mut dir = Found::default();
+            let mut next = Some(first);
+            while let Some(offset) = next.take() {
+                summary.blocks += 1;
+                let block = self.read_raw_block(offset)?;
+                let link = &block[BLOCK_SIZE - ENTRY_SIZE + 118..BLOCK_SIZE - ENTRY_SIZE + 126];
+                let next_block = u64::from_le_bytes(link.try_into().unwrap());
+                let in_bounds = next_block
+                    .checked_add(BLOCK_SIZE as u64)
+                    .is_some_and(|end| next_block >= ROOT_BLOCK && end <= len);
+                if in_bounds && claimed.insert(next_block) {
+                    next = Some(next_block);
+                }
+                for slot in block.chunks_exact(ENTRY_SIZE) {
+                    let Ok(entry) = PackEntry::parse(slot) else {
+                        summary.dropped += 1;
+                        continue;
+                    };
+                    if entry.kind == EntryKind::Empty || entry.name == "." {
+                        continue;
+                    }
+                    // directories linked from anywhere are found, even unaligned ones whose
+                    // `.` entry was not recognized by the search above
+                    let linked = entry.kind == EntryKind::Directory
+                        && !claimed.contains(&entry.position)
+                        && self.is_first_block(entry.position, len)?;
+                    if linked {
+                        claimed.insert(entry.position);
+                        found.insert(entry.position, Found::default());
+                        pending.push(entry.position);
+                    }
+                    if entry.name == ".." {
+                        continue;
+                    }
+                    if invalid_name(&slot[1..82]).is_some() {
+                        summary.dropped += 1;
+                        continue;
+                    }
+                    let in_bounds = entry
+                        .position
+                        .checked_add(u64::from(entry.size))
+                        .is_some_and(|end| end <= len);
+                    match entry.kind {
+                        EntryKind::Directory if found.contains_key(&entry.position) => {
+                            dir.directories.push(entry)
+                        }
+                        EntryKind::File if in_bounds => dir.files.push(entry),
+                        _ => summary.dropped += 1,
+                    }
+                }
+            }
+            found.insert(first, dir);
+        }
+        Ok(found)
+    }
+
+    /// Returns true if a directory starts at `offset` and its first block fits into the container.
+    fn is_first_block(&self, offset: u64, len: u64) -> Result<bool> {
+        if offset.checked_add(BLOCK_SIZE as u64).is_none_or(|end| offset < ROOT_BLOCK || end > len)
+        {
+            return Ok(false);
+        }
+        let mut slot = [0; ENTRY_SIZE];
+        self.read_at(offset, &mut slot)?;
+        self.starts_directory(offset, &slot)
+    }
+
+    /// Returns true if `slot`, the encrypted first slot of the block at `offset`, is a `.` entry
+    /// pointing to the block itself, which every directory starts with. Bytes after the null
+    /// byte of the name are ignored.
+    fn starts_directory(&self, offset: u64, slot: &[u8]) -> Result<bool> {
+        let mut slot: [u8; ENTRY_SIZE] = slot.try_into().unwrap();
+        if let Some(blowfish) = &self.blowfish {
+            blowfish.decrypt(&mut slot)?;
+        }
+        Ok(matches!(
+            PackEntry::parse(&slot),
+            Ok(e) if e.kind == EntryKind::Directory && e.name == "." && e.position == offset
+        ))
+    }
+}
+
+/// Assigns places to the found directories, starting at the root and continuing with the
+/// directories that no other unplaced directory refers to. Parents always come before their
+/// children.
+fn place(found: &BTreeMap<u64, Found>, summary: &mut RepairSummary) -> Vec<Placed> {
+    let mut placed = vec![Placed::new(None, PackEntry::empty())];
+    let mut visited = HashSet::new();
+    if found.contains_key(&ROOT_BLOCK) {
+        place_directory(found, ROOT_BLOCK, 0, &mut visited, &mut placed, summary);
+    }
+    let referenced: HashSet<u64> = found
+        .iter()
+        .filter(|(first, _)| !visited.contains(*first))
+        .flat_map(|(_, dir)| dir.directories.iter().map(|entry| entry.position))
+        .collect();
+    let mut lost_and_found = None;
+    // directories that only refer to each other in a cycle are placed in the second pass
+    for only_unreferenced in [true, false] {
+        for &first in found.keys() {
+            if visited.contains(&first) || (only_unreferenced && referenced.contains(&first)) {
+                continue;
+            }
+            let lost = *lost_and_found.get_or_insert_with(|| lost_and_found_index(&mut placed));
+            let name = unique_name(&placed[lost], format!("{first:#x}"));
+            let idx = add(&mut placed, lost, PackEntry::new(EntryKind::Directory, &name, first, 0));
+            summary.orphans += 1;
+            place_directory(found, first, idx, &mut visited, &mut placed, summary);
+        }
+    }
+    placed
+}
+
+/// Places the files and subdirectories of the directory at `first` into `placed[idx]`.
+fn place_directory(
+    found: &BTreeMap<u64, Found>,
+    first: u64,
+    idx: usize,
+    visited: &mut HashSet<u64>,
+    placed: &mut Vec<Placed>,
+    summary: &mut RepairSummary,
+) {
+    visited.insert(first);
+    if idx != 0 {
+        summary.directories += 1;
+    }
+    let dir = &found[&first];
+    for entry in &dir.files {
+        if placed[idx].names.insert(entry.name_raw.to_ascii_lowercase()) {
+            placed[idx].files.push(entry.clone());
+        } else {
+            summary.dropped += 1;
+        }
+    }
+    for entry in &dir.directories {
+        let key = entry.name_raw.to_ascii_lowercase();
+        if !visited.contains(&entry.position) && !placed[idx].names.contains(&key) {
+            let child = add(placed, idx, entry.clone());
+            place_directory(found, entry.position, child, visited, placed, summary);
+        } else {
+            summary.dropped += 1;
+        }
+    }
+}
+
+impl Placed {
+    fn new(parent: Option<usize>, entry: PackEntry) -> Self {
+        Placed { parent, entry, files: Vec::new(), names: HashSet::new() }
+    }
+}
+
+/// Adds a directory below `placed[parent]` and returns its index.
+fn add(placed: &mut Vec<Placed>, parent: usize, entry: PackEntry) -> usize {
+    placed[parent].names.insert(entry.name_raw.to_ascii_lowercase());
+    placed.push(Placed::new(Some(parent), entry));
+    placed.len() - 1
+}
+
+/// Returns the index of [`LOST_AND_FOUND`], which is added to the root unless the root already
+/// has a directory of that name, as it does when a repaired container is repaired again.
+fn lost_and_found_index(placed: &mut Vec<Placed>) -> usize {
+    let existing =
+        placed.iter().position(|dir| dir.parent == Some(0) && dir.entry.is_named(LOST_AND_FOUND));
+    existing.unwrap_or_else(|| {
+        let name = unique_name(&placed[0], LOST_AND_FOUND.to_owned());
+        add(placed, 0, PackEntry::new(EntryKind::Directory, &name, 0, 0))
+    })
+}
+
+/// Returns `name`, or `name` with a numbered suffix if the directory already has a child of
+/// that name.
+fn unique_name(dir: &Placed, name: String) -> String {
+    let taken = |name: &str| dir.names.contains(name.to_ascii_lowercase().as_bytes());
+    if !taken(&name) {
+        return name;
+    }
+    (1..).map(|n| format!("{name}.{n}")).find(|name| !taken(name)).unwrap()
+}
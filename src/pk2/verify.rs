@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::backend::{check_stored_name, join};
+use crate::error::{Error, Result};
+use crate::pk2::entry::{EntryKind, PackEntry, BLOCK_SIZE, ENTRY_SIZE};
+use crate::pk2::{Pk2, HEADER_SIZE, ROOT_BLOCK};
+
+/// The kind of a [`Problem`] found by [`Pk2::verify`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProblemKind {
+    /// An entry block lies partly or completely past the end of the container.
+    BlockPastEof,
+    /// An entry has a type other than empty, directory or file.
+    InvalidEntryType,
+    /// The `next_block` links of a directory lead back to one of its own blocks.
+    ChainCycle,
+    /// A block belongs to more than one directory, for example because a directory entry points
+    /// to one of its ancestors.
+    SharedBlock,
+    /// The payload of a file extends past the end of the container.
+    PayloadPastEof,
+    /// The payload of a file overlaps another payload, an entry block or the header.
+    Overlap,
+    /// A directory has no `.` entry.
+    MissingSelfLink,
+    /// A directory has no `..` entry.
+    MissingParentLink,
+    /// A `.` or `..` entry points to the wrong block.
+    WrongLink,
+    /// A name is empty, not null-terminated or contains a separator. Names that are not valid
+    /// UTF-8 are not a problem, the raw bytes are kept when the entry is rewritten.
+    InvalidName,
+    /// A directory contains two entries whose names only differ in case.
+    DuplicateName,
+}
+
+impl ProblemKind {
+    /// Returns a stable identifier for the kind, for use in scripts.
+    pub fn code(self) -> &'static str {
+        match self {
+            ProblemKind::BlockPastEof => "block-past-eof",
+            ProblemKind::InvalidEntryType => "invalid-entry-type",
+            ProblemKind::ChainCycle => "chain-cycle",
+            ProblemKind::SharedBlock => "shared-block",
+            ProblemKind::PayloadPastEof => "payload-past-eof",
+            ProblemKind::Overlap => "overlap",
+            ProblemKind::MissingSelfLink => "missing-self-link",
+            ProblemKind::MissingParentLink => "missing-parent-link",
+            ProblemKind::WrongLink => "wrong-link",
+            ProblemKind::InvalidName => "invalid-name",
+            ProblemKind::DuplicateName => "duplicate-name",
+        }
+    }
+}
+
+/// A structural problem of a container.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Path of the affected entry, or of the directory for problems with its blocks.
+    pub path: String,
+    /// Offset of the affected block or payload.
+    pub offset: u64,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:#x} ({}): {}", self.kind.code(), self.offset, self.path, self.message)
+    }
+}
+
+/// The result of [`Pk2::verify`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub problems: Vec<Problem>,
+    /// Size of the container in bytes.
+    pub container_len: u64,
+    /// Number of entry blocks that were read.
+    pub blocks: usize,
+    pub directories: usize,
+    pub files: usize,
+}
+
+impl VerifyReport {
+    /// Returns true if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Serializes the report as a JSON object with the counters and a `problems` array, whose
+    /// elements have the fields `code`, `path`, `offset` and `message`.
+    pub fn to_json(&self) -> String {
+        let problems: Vec<_> = self
+            .problems
+            .iter()
+            .map(|p| {
+                format!(
+                    r#"{{"code":"{}","path":{},"offset":{},"message":{}}}"#,
+                    p.kind.code(),
+                    json_string(&p.path),
+                    p.offset,
+                    json_string(&p.message)
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"ok":{},"container_len":{},"blocks":{},"directories":{},"files":{},"problems":[{}]}}"#,
+            self.is_ok(),
+            self.container_len,
+            self.blocks,
+            self.directories,
+            self.files,
+            problems.join(",")
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// State of a verification run.
+struct Verifier<'a, B> {
+    archive: &'a Pk2<B>,
+    report: VerifyReport,
+    /// The directory every block that was read belongs to.
+    blocks: HashMap<u64, String>,
+    /// Occupied regions of the container.
+    regions: Vec<Region>,
+}
+
+impl<B: Read + Seek> Pk2<B> {
+    /// Walks all entry blocks reachable from the root and reports structural problems.
+    ///
+    /// Problems with the data are collected in the report instead of aborting the check, so a
+    /// single run lists everything that is wrong with a container. Only failing reads of the
+    /// underlying stream and a wrong key for the blocks are returned as errors.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let container_len = self.stream().seek(SeekFrom::End(0))?;
+        let mut verifier = Verifier {
+            archive: self,
+            report: VerifyReport { container_len, ..VerifyReport::default() },
+            blocks: HashMap::new(),
+            regions: vec![Region {
+                start: 0,
+                end: HEADER_SIZE as u64,
+                owner: Owner::Header,
+                path: "/".to_owned(),
+            }],
+        };
+        verifier.directory("/", ROOT_BLOCK, ROOT_BLOCK)?;
+        verifier.overlaps();
+        Ok(verifier.report)
+    }
+}
+
+impl<B: Read + Seek> Verifier<'_, B> {
+    fn problem(&mut self, kind: ProblemKind, path: &str, offset: u64, message: impl Into<String>) {
+        self.report.problems.push(Problem {
+            kind,
+            path: path.to_owned(),
+            offset,
+            message: message.into(),
+        });
+    }
+
+    /// Checks the directory whose first block is at `first` and everything below it.
+    fn directory(&mut self, path: &str, first: u64, parent: u64) -> Result<()> {
+        let (mut has_self, mut has_parent) = (false, false);
+        let mut names = HashSet::new();
+        let mut children = Vec::new();
+        let mut chain = HashSet::new();
+        let mut next = Some(first);
+        while let Some(offset) = next.take() {
+            if let Some(owner) = self.blocks.get(&offset) {
+                let (kind, message) = if chain.contains(&offset) {
+                    (
+                        ProblemKind::ChainCycle,
+                        "block chain leads back to one of its blocks".to_owned(),
+                    )
+                } else {
+                    (ProblemKind::SharedBlock, format!("block already belongs to {owner}"))
+                };
+                self.problem(kind, path, offset, message);
+                break;
+            }
+            if offset
+                .checked_add(BLOCK_SIZE as u64)
+                .is_none_or(|end| end > self.report.container_len)
+            {
+                self.problem(ProblemKind::BlockPastEof, path, offset, "entry block past the end");
+                break;
+            }
+            chain.insert(offset);
+            self.blocks.insert(offset, path.to_owned());
+            self.regions.push(Region {
+                start: offset,
+                end: offset + BLOCK_SIZE as u64,
+                owner: Owner::Block,
+                path: path.to_owned(),
+            });
+            self.report.blocks += 1;
+
+            let raw = self.archive.read_raw_block(offset)?;
+            // the link is read even if the last slot is invalid, so the rest of the chain is
+            // still checked
+            let link = &raw[BLOCK_SIZE - ENTRY_SIZE + 118..BLOCK_SIZE - ENTRY_SIZE + 126];
+            next = Some(u64::from_le_bytes(link.try_into().unwrap())).filter(|&next| next != 0);
+            for (idx, slot) in raw.chunks_exact(ENTRY_SIZE).enumerate() {
+                let slot_offset = offset + (idx * ENTRY_SIZE) as u64;
+                let entry = match PackEntry::parse(slot) {
+                    Ok(entry) => entry,
+                    Err(reason) => {
+                        let message = format!("{reason} {} in slot {idx}", slot[0]);
+                        self.problem(ProblemKind::InvalidEntryType, path, slot_offset, message);
+                        continue;
+                    }
+                };
+                if entry.kind == EntryKind::Empty {
+                    continue;
+                }
+                let entry_path = join(path, &entry.name);
+                let link = match entry.name.as_str() {
+                    "." if entry.kind == EntryKind::Directory => Some((&mut has_self, first)),
+                    ".." if entry.kind == EntryKind::Directory => Some((&mut has_parent, parent)),
+                    _ => None,
+                };
+                if let Some((seen, expected)) = link {
+                    *seen = true;
+                    // the root is its own parent, but some tools leave its `..` entry zeroed
+                    let root_link = first == ROOT_BLOCK && entry.position == 0;
+                    if entry.position != expected && !root_link {
+                        let message =
+                            format!("points to {:#x} instead of {expected:#x}", entry.position);
+                        self.problem(ProblemKind::WrongLink, &entry_path, slot_offset, message);
+                    }
+                    continue;
+                }
+                if let Some(reason) = invalid_name(&slot[1..82]) {
+                    self.problem(ProblemKind::InvalidName, &entry_path, slot_offset, reason);
+                }
+                if !names.insert(entry.name_raw.to_ascii_lowercase()) {
+                    let message = "another entry in the directory has the same name";
+                    self.problem(ProblemKind::DuplicateName, &entry_path, slot_offset, message);
+                }
+                match entry.kind {
+                    EntryKind::Directory => children.push((entry_path, entry.position)),
+                    _ => self.file(&entry_path, &entry),
+                }
+            }
+        }
+        if !chain.contains(&first) {
+            // the directory itself could not be read, which was already reported
+            return Ok(());
+        }
+        if !has_self {
+            self.problem(ProblemKind::MissingSelfLink, path, first, "directory has no . entry");
+        }
+        if !has_parent {
+            self.problem(ProblemKind::MissingParentLink, path, first, "directory has no .. entry");
+        }
+        self.report.directories += 1;
+        for (child, position) in children {
+            self.directory(&child, position, first)?;
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, path: &str, entry: &PackEntry) {
+        self.report.files += 1;
+        match entry.position.checked_add(u64::from(entry.size)) {
+            Some(end) if end <= self.report.container_len => {
+                if entry.size != 0 {
+                    self.regions.push(Region {
+                        start: entry.position,
+                        end,
+                        owner: Owner::Payload,
+                        path: path.to_owned(),
+                    });
+                }
+            }
+            _ => {
+                let message = format!("payload of {} bytes extends past the end", entry.size);
+                self.problem(ProblemKind::PayloadPastEof, path, entry.position, message);
+            }
+        }
+    }
+
+    /// Reports every region that overlaps another one. Payloads are reported in favor of blocks
+    /// and blocks in favor of the header, with the path of the file or directory they belong to.
+    fn overlaps(&mut self) {
+        let mut regions = std::mem::take(&mut self.regions);
+        regions.sort_by_key(|region| (region.start, region.end, region.owner));
+        let mut furthest: Option<&Region> = None;
+        let mut problems = Vec::new();
+        for region in &regions {
+            if let Some(other) = furthest.filter(|other| region.start < other.end) {
+                // the same problem is produced no matter which of the two comes first
+                let (reported, other) =
+                    if region.owner >= other.owner { (region, other) } else { (other, region) };
+                let message = format!("overlaps {}", other.describe());
+                problems.push((reported.path.clone(), region.start, message));
+            }
+            if furthest.is_none_or(|other| region.end > other.end) {
+                furthest = Some(region);
+            }
+        }
+        for (path, offset, message) in problems {
+            self.problem(ProblemKind::Overlap, &path, offset, message);
+        }
+    }
+}
+
+/// What occupies a [`Region`], in the order in which they are preferred for reporting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Owner {
+    Header,
+    Block,
+    Payload,
+}
+
+/// An occupied region of the container.
+struct Region {
+    start: u64,
+    end: u64,
+    owner: Owner,
+    /// Path of the file or directory the region belongs to.
+    path: String,
+}
+
+impl Region {
+    fn describe(&self) -> String {
+        match self.owner {
+            Owner::Header => "the header".to_owned(),
+            Owner::Block => format!("a block of {}", self.path),
+            Owner::Payload => format!("the payload of {}", self.path),
+        }
+    }
+}
+
+/// Describes what is wrong with a name, `field` is the name field of the entry. The stored
+/// bytes are checked rather than the decoded name, which grows when bytes that are not valid
+/// UTF-8 are replaced.
+pub(crate) fn invalid_name(field: &[u8]) -> Option<&'static str> {
+    let Some(len) = field.iter().position(|&b| b == 0) else {
+        return Some("name is not null-terminated");
+    };
+    match check_stored_name(&field[..len], "") {
+        Ok(()) => None,
+        Err(Error::NameTooLong(_)) => Some("name is too long"),
+        Err(_) => Some("name is empty, reserved or contains a separator"),
+    }
+}
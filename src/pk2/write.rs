@@ -0,0 +1,425 @@
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::backend::{check_name, check_stored_name, ArchivePath};
+use crate::error::{Error, Result};
+use crate::pk2::blowfish::Blowfish;
+use crate::pk2::entry::{EntryKind, FileTime, PackBlock, PackEntry, BLOCK_SIZE};
+use crate::pk2::file::FileMut;
+use crate::pk2::header::Pk2Header;
+use crate::pk2::{Pk2, ROOT_BLOCK};
+
+impl Pk2<fs::File> {
+    /// Creates a new container and opens it, an existing file at `path` is overwritten
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path of the container on the host filesystem
+    /// * password - Password for accessing the new container
+    pub fn create<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        let file =
+            fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let archive = Self::create_in(file.try_clone()?, password)?;
+        Ok(Pk2 { reader: Some(file), ..archive })
+    }
+}
+
+impl<B: Read + Write + Seek> Pk2<B> {
+    /// Writes a new, empty container to the given stream and opens it.
+    pub fn create_in(mut stream: B, password: &str) -> Result<Self> {
+        let blowfish = Blowfish::from_password(password)?;
+        stream.seek(SeekFrom::Start(0))?;
+        Pk2Header::new(&blowfish).write_to(&mut stream)?;
+        let archive = Pk2::new(stream, Some(blowfish));
+        archive.write_block(&directory_block(ROOT_BLOCK, ROOT_BLOCK))?;
+        Ok(archive)
+    }
+
+    /// Creates a directory and all of its missing parents, succeeds if it already exists.
+    pub fn create_directory(&self, path: &str) -> Result<()> {
+        let _edit = self.lock_edits();
+        self.ensure_directory(&self.resolve(path)?).map(drop)
+    }
+
+    /// Deletes a file, the space of its payload is added to the [free
+    /// regions](Pk2::free_regions). Handles that still have the file open keep reading its old
+    /// contents, the space is only reused once they are dropped.
+    pub fn delete_file(&self, path: &str) -> Result<()> {
+        let _edit = self.lock_edits();
+        let (mut block, idx) = self.find_slot(path)?;
+        let entry = block.entries[idx].clone();
+        if entry.kind != EntryKind::File {
+            return Err(Error::NotAFile(path.to_owned()));
+        }
+        block.set_entry(idx, PackEntry::empty());
+        self.write_block(&block)?;
+        self.release(payload(&entry));
+        Ok(())
+    }
+
+    /// Deletes an empty directory, the space of its blocks is added to the [free
+    /// regions](Pk2::free_regions).
+    pub fn delete_directory(&self, path: &str) -> Result<()> {
+        let _edit = self.lock_edits();
+        let (mut block, idx) = self.find_slot(path)?;
+        let entry = &block.entries[idx];
+        if entry.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        let chain = self.read_chain(entry.position)?;
+        let is_empty = chain
+            .iter()
+            .flat_map(|block| &block.entries)
+            .all(|e| e.kind == EntryKind::Empty || e.name == "." || e.name == "..");
+        if !is_empty {
+            return Err(Error::DirectoryNotEmpty(path.to_owned()));
+        }
+        block.set_entry(idx, PackEntry::empty());
+        self.write_block(&block)?;
+        for block in &chain {
+            self.release(block_region(block.offset));
+        }
+        Ok(())
+    }
+
+    /// Deletes a directory together with everything below it. The space of all payloads and
+    /// blocks in the subtree is added to the [free regions](Pk2::free_regions).
+    pub fn delete_directory_all(&self, path: &str) -> Result<()> {
+        let _edit = self.lock_edits();
+        let (mut block, idx) = self.find_slot(path)?;
+        let entry = &block.entries[idx];
+        if entry.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(path.to_owned()));
+        }
+        // collect everything first, so that a corrupt subtree leaves the archive untouched
+        let mut regions = Vec::new();
+        self.collect_tree(entry.position, &mut HashSet::new(), &mut regions)?;
+        block.set_entry(idx, PackEntry::empty());
+        self.write_block(&block)?;
+        for region in regions {
+            self.release(region);
+        }
+        Ok(())
+    }
+
+    /// Renames or moves a file or directory, like [`std::fs::rename`]. The parent directory of
+    /// `to` has to exist and `to` itself must not, unless it only differs from `from` in case.
+    /// A directory keeps its blocks, only its `..` entry is pointed at the new parent.
+    pub fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let _edit = self.lock_edits();
+        let from_path = self.cwd.resolve(from)?;
+        let to_path = self.cwd.resolve(to)?;
+        let (Some(from_parent), Some(name)) = (from_path.parent(), to_path.name()) else {
+            return Err(Error::InvalidPath(if from_path.is_root() { from } else { to }.to_owned()));
+        };
+        let src_dir = self.lookup_components(from_parent.components())?;
+        if src_dir.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(from_parent.to_string()));
+        }
+        let (block, idx) = self
+            .find_in_directory(src_dir.position, from_path.name().unwrap_or_default())?
+            .ok_or_else(|| Error::NotFound(from.to_owned()))?;
+        let mut entry = block.entries[idx].clone();
+        if entry.kind == EntryKind::Directory
+            && to_path.starts_with(&from_path)
+            && to_path != from_path
+        {
+            return Err(Error::InvalidPath(to.to_owned()));
+        }
+
+        let to_parent = to_path.parent().unwrap_or_else(ArchivePath::root);
+        let dst_dir = self.lookup_components(to_parent.components())?;
+        if dst_dir.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(to_parent.to_string()));
+        }
+        match self.find_in_directory(dst_dir.position, name)? {
+            Some((existing, existing_idx))
+                if (existing.offset, existing_idx) == (block.offset, idx) => {}
+            Some(_) => return Err(Error::AlreadyExists(to.to_owned())),
+            None => {}
+        }
+
+        // moving an entry under the same name keeps the stored bytes of names in other encodings
+        if entry.name != name {
+            entry.set_name(name);
+        }
+        if dst_dir.position == src_dir.position {
+            return self.update_entry(block.offset, idx, entry);
+        }
+        // the entry is added to its new directory before it is removed from the old one, so it
+        // cannot get lost halfway
+        self.insert_entry(dst_dir.position, entry.clone())?;
+        self.update_entry(block.offset, idx, PackEntry::empty())?;
+        if entry.kind == EntryKind::Directory {
+            let mut first = self.read_block(entry.position)?;
+            let parent_idx = first
+                .entries
+                .iter()
+                .position(|e| e.name == "..")
+                .ok_or(Error::CorruptBlock { offset: first.offset, reason: "missing .. entry" })?;
+            let mut parent = first.entries[parent_idx].clone();
+            parent.position = dst_dir.position;
+            first.set_entry(parent_idx, parent);
+            self.write_block(&first)?;
+        }
+        Ok(())
+    }
+
+    /// Creates a file inside the container, missing parent directories are created and an
+    /// existing file is truncated. The contents are written back when the returned handle is
+    /// flushed or dropped.
+    pub fn create_file(&self, path: &str) -> Result<FileMut<'_, B>> {
+        let (components, entry) = self.create_entry(path)?;
+        Ok(FileMut::new(self, components, entry))
+    }
+
+    /// Finds the file at `path` or inserts an empty entry for it, creating missing parent
+    /// directories. Returns the components of the absolute path and the entry.
+    pub(crate) fn create_entry(&self, path: &str) -> Result<(Vec<String>, PackEntry)> {
+        let mut components = self.resolve(path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        let _edit = self.lock_edits();
+        let dir = self.ensure_directory(&components)?;
+        let entry = match self.find_in_directory(dir, &name)? {
+            Some((block, idx)) if block.entries[idx].kind == EntryKind::File => {
+                block.entries[idx].clone()
+            }
+            Some(_) => return Err(Error::AlreadyExists(path.to_owned())),
+            None => {
+                let entry = PackEntry::new(EntryKind::File, &name, 0, 0);
+                self.insert_entry(dir, entry.clone())?;
+                entry
+            }
+        };
+        components.push(name);
+        Ok((components, entry))
+    }
+
+    /// Replaces the contents of the file at the given components with exactly `len` bytes read
+    /// from `src` and sets its times, returning the updated entry. The data is copied in chunks,
+    /// so it does not have to fit into memory.
+    ///
+    /// The entry is looked up again, the file may have been replaced since its handle was
+    /// created, so the payload of the entry as it is now gets reused or released.
+    pub(crate) fn store_file(
+        &self,
+        components: &[String],
+        src: impl Read,
+        len: u64,
+        create_time: FileTime,
+        modify_time: FileTime,
+    ) -> Result<PackEntry> {
+        let size = u32::try_from(len).map_err(|_| Error::FileTooLarge(len))?;
+        let _edit = self.lock_edits();
+        let (mut block, idx) = self.find_file(components)?;
+        let mut entry = block.entries[idx].clone();
+        let old = payload(&entry);
+        // reuse the old payload's space if the new one fits and nobody reads it, otherwise
+        // allocate new space
+        let reuse = entry.position != 0 && size <= entry.size && !self.free_list().is_open(&old);
+        let position = if reuse { entry.position } else { self.allocate(len)? };
+        if let Err(e) = self.copy_from(position, src, len) {
+            if !reuse {
+                self.release(position..position + len);
+            }
+            return Err(e);
+        }
+        let released = if reuse { old.start + len..old.end } else { old };
+        entry.position = position;
+        entry.size = size;
+        entry.create_time = create_time;
+        entry.modify_time = modify_time;
+        block.set_entry(idx, entry.clone());
+        self.write_block(&block)?;
+        if released.start != 0 {
+            self.release(released);
+        }
+        Ok(entry)
+    }
+
+    /// Copies exactly `len` bytes from `src` to the given offset of the container.
+    fn copy_from(&self, mut offset: u64, mut src: impl Read, len: u64) -> Result<()> {
+        let mut buf = vec![0; (len as usize).min(64 * 1024)];
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = buf.len().min(remaining as usize);
+            src.read_exact(&mut buf[..n])?;
+            self.write_at(offset, &buf[..n])?;
+            offset += n as u64;
+            remaining -= n as u64;
+        }
+        Ok(())
+    }
+
+    /// Finds the block and index of the file at the given components.
+    pub(crate) fn find_file(&self, components: &[String]) -> Result<(PackBlock, usize)> {
+        let path = || components.join("/");
+        let (name, parents) = components.split_last().ok_or_else(|| Error::NotAFile(path()))?;
+        let dir = self.lookup_components(parents)?;
+        if dir.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(parents.join("/")));
+        }
+        match self.find_in_directory(dir.position, name)? {
+            Some((block, idx)) if block.entries[idx].kind == EntryKind::File => Ok((block, idx)),
+            Some(_) => Err(Error::NotAFile(path())),
+            None => Err(Error::NotFound(path())),
+        }
+    }
+
+    /// Walks down the given directories starting at the root, creating the ones that are missing.
+    /// Returns the offset of the last directory's first block.
+    fn ensure_directory(&self, components: &[String]) -> Result<u64> {
+        let mut dir = ROOT_BLOCK;
+        for (depth, name) in components.iter().enumerate() {
+            dir = match self.find_in_directory(dir, name)? {
+                Some((block, idx)) if block.entries[idx].kind == EntryKind::Directory => {
+                    block.entries[idx].position
+                }
+                Some(_) => return Err(Error::AlreadyExists(components[..=depth].join("/"))),
+                None => {
+                    check_name(name)?;
+                    self.insert_directory(dir, PackEntry::new(EntryKind::Directory, name, 0, 0))?
+                }
+            };
+        }
+        Ok(dir)
+    }
+
+    /// Creates a directory for `entry` in the directory whose first block is at `parent`, keeping
+    /// the name and times of the entry. Returns the offset of its first block.
+    pub(crate) fn insert_directory(&self, parent: u64, mut entry: PackEntry) -> Result<u64> {
+        let offset = self.allocate(BLOCK_SIZE as u64)?;
+        self.write_block(&directory_block(offset, parent))?;
+        entry.position = offset;
+        entry.size = 0;
+        self.insert_entry(parent, entry)?;
+        Ok(offset)
+    }
+
+    /// Adds a file for `entry` to the directory whose first block is at `dir`, copying the size
+    /// of the entry in bytes from `src`.
+    pub(crate) fn insert_file(&self, dir: u64, mut entry: PackEntry, src: impl Read) -> Result<()> {
+        let len = u64::from(entry.size);
+        entry.position = self.allocate(len)?;
+        self.copy_from(entry.position, src, len)?;
+        self.insert_entry(dir, entry).map(drop)
+    }
+
+    /// Finds the block and index of the entry at the given path.
+    fn find_slot(&self, path: &str) -> Result<(PackBlock, usize)> {
+        let mut components = self.resolve(path)?;
+        let name = components.pop().ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        let dir = self.lookup_components(&components)?;
+        if dir.kind != EntryKind::Directory {
+            return Err(Error::NotADirectory(components.join("/")));
+        }
+        self.find_in_directory(dir.position, &name)?.ok_or_else(|| Error::NotFound(path.to_owned()))
+    }
+
+    /// Puts the entry into the first free slot of the directory, growing the block chain if all
+    /// slots are taken. Returns the block offset and index the entry was written to.
+    fn insert_entry(&self, dir: u64, entry: PackEntry) -> Result<(u64, usize)> {
+        check_stored_name(&entry.name_raw, &entry.name)?;
+        let mut blocks = self.read_chain(dir)?;
+        for block in &mut blocks {
+            if let Some(idx) = block.entries.iter().position(|e| e.kind == EntryKind::Empty) {
+                block.set_entry(idx, entry);
+                self.write_block(block)?;
+                return Ok((block.offset, idx));
+            }
+        }
+        let offset = self.allocate(BLOCK_SIZE as u64)?;
+        let mut block = PackBlock::empty(offset);
+        block.set_entry(0, entry);
+        self.write_block(&block)?;
+        let last = blocks.last_mut().unwrap();
+        last.set_next_block(offset);
+        self.write_block(last)?;
+        Ok((offset, 0))
+    }
+
+    /// Overwrites the entry at the given location.
+    pub(crate) fn update_entry(&self, block: u64, idx: usize, entry: PackEntry) -> Result<()> {
+        let mut block = self.read_block(block)?;
+        block.set_entry(idx, entry);
+        self.write_block(&block)
+    }
+
+    pub(crate) fn write_block(&self, block: &PackBlock) -> Result<()> {
+        let mut buf = *block.raw();
+        if let Some(blowfish) = &self.blowfish {
+            blowfish.encrypt(&mut buf)?;
+        }
+        self.write_at(block.offset, &buf)
+    }
+
+    pub(crate) fn write_at(&self, offset: u64, buf: &[u8]) -> Result<()> {
+        let mut stream = self.stream();
+        stream.seek(SeekFrom::Start(offset))?;
+        stream.write_all(buf)?;
+        Ok(())
+    }
+
+    /// Returns the offset of `len` unused bytes, taken from the free regions if possible.
+    fn allocate(&self, len: u64) -> Result<u64> {
+        match self.free_list().allocate(len) {
+            Some(offset) => Ok(offset),
+            None => self.stream_len(),
+        }
+    }
+
+    pub(crate) fn release(&self, region: Range<u64>) {
+        self.free_list().release(region);
+    }
+
+    /// Collects the payloads and blocks of the directory whose first block is at `dir` and of
+    /// everything below it.
+    fn collect_tree(
+        &self,
+        dir: u64,
+        visited: &mut HashSet<u64>,
+        regions: &mut Vec<Range<u64>>,
+    ) -> Result<()> {
+        if !visited.insert(dir) {
+            return Err(Error::CorruptBlock { offset: dir, reason: "directory contains itself" });
+        }
+        for block in self.read_chain(dir)? {
+            for entry in &block.entries {
+                match entry.kind {
+                    EntryKind::File => regions.push(payload(entry)),
+                    EntryKind::Directory if entry.name != "." && entry.name != ".." => {
+                        self.collect_tree(entry.position, visited, regions)?;
+                    }
+                    _ => {}
+                }
+            }
+            regions.push(block_region(block.offset));
+        }
+        Ok(())
+    }
+
+    fn stream_len(&self) -> Result<u64> {
+        Ok(self.stream().seek(SeekFrom::End(0))?)
+    }
+}
+
+/// Returns the region occupied by the payload of a file.
+pub(crate) fn payload(entry: &PackEntry) -> Range<u64> {
+    entry.position..entry.position.saturating_add(u64::from(entry.size))
+}
+
+fn block_region(offset: u64) -> Range<u64> {
+    offset..offset + BLOCK_SIZE as u64
+}
+
+/// Creates the first block of a directory, which starts with the `.` and `..` entries.
+pub(crate) fn directory_block(offset: u64, parent: u64) -> PackBlock {
+    let mut block = PackBlock::empty(offset);
+    block.set_entry(0, PackEntry::new(EntryKind::Directory, ".", offset, 0));
+    block.set_entry(1, PackEntry::new(EntryKind::Directory, "..", parent, 0));
+    block
+}
@@ -1,20 +1,24 @@
+use std::convert::TryFrom;
 use std::ffi::CStr;
 
 use winapi::ctypes::{c_char, c_int};
 use winapi::shared::minwindef::FILETIME;
 use winapi::um::minwinbase::WIN32_FIND_DATAA;
 
+use crate::error::{Error, Result};
+
 pub enum Entry {
     Directory = 1,
     File = 2,
 }
 
-impl From<i8> for Entry {
-    fn from(i: i8) -> Entry {
+impl TryFrom<i8> for Entry {
+    type Error = Error;
+    fn try_from(i: i8) -> Result<Entry> {
         match i {
-            1 => Entry::Directory,
-            2 => Entry::File,
-            _ => panic!("Invalid Entry type"),
+            1 => Ok(Entry::Directory),
+            2 => Ok(Entry::File),
+            _ => Err(Error::InvalidEntryType(i)),
         }
     }
 }
@@ -35,22 +39,30 @@ pub struct ResultEntry {
     find_dataa: WIN32_FIND_DATAA,
 }
 
+impl Default for ResultEntry {
+    fn default() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
 impl ResultEntry {
-    pub fn filename(&self) -> Result<&str, ::std::str::Utf8Error> {
+    pub fn filename(&self) -> Result<&str> {
         let cstring = unsafe { CStr::from_ptr(self.filename.as_ptr()) };
-        cstring.to_str()
+        cstring.to_str().map_err(|_| Error::InvalidUtf8)
     }
 
     pub fn filename_as_ptr(&self) -> *const c_char {
         self.filename.as_ptr()
     }
 
-    pub fn typ(&self) -> Entry {
-        Entry::from(self.typ)
+    pub fn typ(&self) -> Result<Entry> {
+        Entry::try_from(self.typ)
     }
 
-    pub fn size(&self) -> c_int {
-        self.size
+    /// Returns the size of the file, the dll stores the unsigned 32-bit size of the entry in a
+    /// signed int.
+    pub fn size(&self) -> u64 {
+        u64::from(self.size as u32)
     }
 
     pub fn file_time(&self) -> FILETIME {
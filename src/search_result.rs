@@ -1,13 +1,23 @@
 use winapi::ctypes::{c_char, c_int, c_uchar};
 use winapi::shared::ntdef::HANDLE;
 
+use crate::backend::{join, DirEntry};
+use crate::error::Result;
 use crate::file_manager::GFXFileManager;
+use crate::pk2::{EntryKind, FileTime};
+use crate::result_entry::{Entry, ResultEntry};
 
 pub struct SearchResult<'a> {
     inner: GFXSearchResult,
     file_manager: &'a GFXFileManager,
 }
 
+impl<'a> SearchResult<'a> {
+    pub fn new(file_manager: &'a GFXFileManager) -> Self {
+        SearchResult { inner: unsafe { std::mem::zeroed() }, file_manager }
+    }
+}
+
 impl SearchResult<'_> {
     pub(crate) fn inner_mut(&mut self) -> &mut GFXSearchResult {
         &mut self.inner
@@ -28,6 +38,64 @@ impl Drop for SearchResult<'_> {
     }
 }
 
+/// Iterator over the entries of a directory, returned by [`GFXFileManager::read_dir`].
+pub struct ReadDir<'a> {
+    file_manager: &'a GFXFileManager,
+    search: SearchResult<'a>,
+    entry: ResultEntry,
+    path: String,
+    /// Whether `entry` holds an entry that has not been returned yet.
+    pending: bool,
+}
+
+impl<'a> ReadDir<'a> {
+    pub(crate) fn new(
+        file_manager: &'a GFXFileManager,
+        search: SearchResult<'a>,
+        entry: ResultEntry,
+        path: &str,
+    ) -> Self {
+        ReadDir { file_manager, search, entry, path: path.to_owned(), pending: true }
+    }
+
+    fn dir_entry(&self) -> Result<Option<DirEntry>> {
+        let name = self.entry.filename()?;
+        if name == "." || name == ".." {
+            return Ok(None);
+        }
+        let find_data = self.entry.find_dataa();
+        Ok(Some(DirEntry {
+            path: join(&self.path, name),
+            name: name.to_owned(),
+            kind: match self.entry.typ()? {
+                Entry::Directory => EntryKind::Directory,
+                Entry::File => EntryKind::File,
+            },
+            size: self.entry.size(),
+            create_time: FileTime::from(find_data.ftCreationTime),
+            modify_time: FileTime::from(find_data.ftLastWriteTime),
+            access_time: FileTime::from(find_data.ftLastAccessTime),
+            position: None,
+        }))
+    }
+}
+
+impl Iterator for ReadDir<'_> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        while self.pending {
+            // convert the current entry before the next call overwrites it
+            let entry = self.dir_entry().transpose();
+            self.pending = self.file_manager.find_next_file(&mut self.search, &mut self.entry);
+            if entry.is_some() {
+                return entry;
+            }
+        }
+        None
+    }
+}
+
 #[repr(C)]
 #[allow(non_snake_case)]
 pub(crate) struct GFXSearchResult {
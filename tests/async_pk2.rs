@@ -0,0 +1,82 @@
+#![cfg(feature = "tokio")]
+
+use std::io::{SeekFrom, Write};
+
+use gfxfilemanager::pk2::{AsyncPk2, DEFAULT_PASSWORD};
+use gfxfilemanager::{Error, Overwrite, Pk2};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+fn contents(i: usize) -> Vec<u8> {
+    (0..70_000 + i * 1013).map(|b| (b * 7 + i) as u8).collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_reads() {
+    let dir = std::env::temp_dir().join(format!("gfxfilemanager-async-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("test.pk2");
+    let archive = Pk2::create(&path, DEFAULT_PASSWORD).unwrap();
+    for i in 0..16 {
+        archive
+            .create_file(&format!("Media/file{i}.bin"))
+            .unwrap()
+            .write_all(&contents(i))
+            .unwrap();
+    }
+    archive.create_file("Media/small.txt").unwrap().write_all(b"0123456789").unwrap();
+    drop(archive);
+
+    let archive = AsyncPk2::open(&path, DEFAULT_PASSWORD).await.unwrap();
+    let entries = archive.read_dir("media").await.unwrap();
+    assert_eq!(entries.len(), 17);
+    assert!(matches!(archive.read_dir("missing").await, Err(Error::NotFound(_))));
+    assert!(matches!(archive.open_file("media").await, Err(Error::NotAFile(_))));
+
+    let tasks: Vec<_> = (0..16)
+        .map(|i| {
+            let archive = archive.clone();
+            tokio::spawn(async move {
+                let mut file = archive.open_file(&format!("media/FILE{i}.bin")).await.unwrap();
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf).await.unwrap();
+                assert_eq!(buf, contents(i));
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut file = archive.open_file("Media/small.txt").await.unwrap();
+    assert_eq!(file.len(), 10);
+    assert_eq!(file.seek(SeekFrom::End(-4)).await.unwrap(), 6);
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).await.unwrap();
+    assert_eq!(buf, "6789");
+    file.seek(SeekFrom::Start(2)).await.unwrap();
+    let mut buf = [0; 3];
+    file.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"234");
+    assert!(file.seek(SeekFrom::Current(-10)).await.is_err());
+
+    assert_eq!(archive.bytes("media/file3.bin").await.unwrap(), contents(3));
+    let dst = dir.join("out/file5.bin");
+    let copied = archive.extract_file("media/file5.bin", &dst).await.unwrap();
+    assert_eq!(copied, contents(5).len() as u64);
+    assert_eq!(std::fs::read(&dst).unwrap(), contents(5));
+
+    let summary = archive.extract("media", dir.join("tree")).await.unwrap();
+    assert_eq!(summary.written.len(), 17);
+    assert_eq!(std::fs::read(dir.join("tree/file7.bin")).unwrap(), contents(7));
+    let summary = archive
+        .extract_with("/", dir.join("tree"), |extract| {
+            extract.include("**/small.txt").overwrite(Overwrite::Never)
+        })
+        .await
+        .unwrap();
+    assert_eq!((summary.written.len(), summary.skipped.len()), (1, 0));
+    assert_eq!(std::fs::read(dir.join("tree/Media/small.txt")).unwrap(), b"0123456789");
+
+    drop(archive);
+    std::fs::remove_dir_all(dir).unwrap();
+}
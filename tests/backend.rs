@@ -0,0 +1,325 @@
+//! Checks that all native backends behave the same through the `FileManager` trait.
+
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::{Error, FileManager, HostFileManager, MemoryArchive, Pk2, WalkEvent};
+
+fn new_archive() -> Pk2<Cursor<Vec<u8>>> {
+    Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gfxfilemanager-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn host_archive(name: &str) -> HostFileManager {
+    let dir = temp_dir(&format!("host-{name}"));
+    std::fs::create_dir_all(&dir).unwrap();
+    HostFileManager::new(dir).unwrap()
+}
+
+fn write<F: FileManager>(fm: &F, path: &str, data: &[u8]) {
+    let mut file = fm.create_file(path).unwrap();
+    file.write_all(data).unwrap();
+    file.flush().unwrap();
+}
+
+fn read<F: FileManager>(fm: &F, path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    fm.open_file(path).unwrap().read_to_end(&mut buf).unwrap();
+    buf
+}
+
+fn names<F: FileManager>(fm: &F, pattern: &str) -> Vec<String> {
+    let mut names: Vec<_> = fm.find_files(pattern).unwrap().into_iter().map(|e| e.name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn current_directory() {
+    check_current_directory(&mut new_archive());
+    check_current_directory(&mut MemoryArchive::new());
+    check_current_directory(&mut host_archive("cwd"));
+}
+
+fn check_current_directory<F: FileManager>(fm: &mut F) {
+    write(fm, "Media/textdata/a.txt", b"a");
+    fm.change_directory("media/TEXTDATA").unwrap();
+    assert_eq!(fm.current_directory().unwrap(), "media/TEXTDATA");
+    assert_eq!(read(fm, "a.txt"), b"a");
+    write(fm, "b.txt", b"b");
+    assert_eq!(read(fm, "/Media/textdata/b.txt"), b"b");
+    fm.change_directory("..").unwrap();
+    assert_eq!(read(fm, "textdata\\b.txt"), b"b");
+    assert!(matches!(fm.change_directory("textdata/a.txt"), Err(Error::NotADirectory(_))));
+    fm.reset_directory().unwrap();
+    assert_eq!(fm.current_directory().unwrap(), "");
+    assert!(fm.file_exists("media/textdata").unwrap());
+    assert!(!fm.file_exists("textdata").unwrap());
+}
+
+#[test]
+fn find_files() {
+    check_find_files(&new_archive());
+    check_find_files(&MemoryArchive::new());
+    check_find_files(&host_archive("find"));
+}
+
+fn check_find_files<F: FileManager>(fm: &F) {
+    write(fm, "dir/a.txt", b"");
+    write(fm, "dir/b.TXT", b"");
+    write(fm, "dir/c.dds", b"");
+    fm.create_directory("dir/sub").unwrap();
+    assert_eq!(names(fm, "dir/*"), ["a.txt", "b.TXT", "c.dds", "sub"]);
+    assert_eq!(names(fm, "dir/*.txt"), ["a.txt", "b.TXT"]);
+    assert_eq!(names(fm, "dir/?.dds"), ["c.dds"]);
+    assert_eq!(names(fm, "/"), ["dir"]);
+    let entries = fm.find_files("dir/sub").unwrap();
+    assert!(entries[0].is_dir());
+}
+
+#[test]
+fn read_dir() {
+    check_read_dir(&new_archive());
+    check_read_dir(&MemoryArchive::new());
+    check_read_dir(&host_archive("read-dir"));
+}
+
+fn check_read_dir<F: FileManager>(fm: &F) {
+    write(fm, "Dir/a.txt", b"abc");
+    assert_eq!(fm.bytes("dir/A.TXT").unwrap(), b"abc");
+    fm.create_directory("dir/sub").unwrap();
+    let mut entries: Vec<_> = fm.read_dir("DIR").unwrap().map(Result::unwrap).collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].path.as_str(), entries[0].size), ("DIR/a.txt", 3));
+    assert!(entries[0].is_file());
+    assert_eq!((entries[1].path.as_str(), entries[1].size), ("DIR/sub", 0));
+    assert!(entries[1].is_dir());
+    assert!(entries[0].modify_time.0 > 0);
+
+    assert_eq!(fm.read_dir("dir/sub").unwrap().count(), 0);
+    assert!(matches!(fm.read_dir("missing"), Err(Error::NotFound(_))));
+    assert!(matches!(fm.read_dir("dir/a.txt"), Err(Error::NotADirectory(_))));
+}
+
+#[test]
+fn walk() {
+    check_walk(&new_archive());
+    check_walk(&MemoryArchive::new());
+    check_walk(&host_archive("walk"));
+}
+
+/// Collects the walk as `+dir`, `-dir` and file paths, checking that the events nest properly.
+fn walk_paths<F: FileManager>(walk: gfxfilemanager::Walk<'_, F>) -> Vec<String> {
+    let mut open = Vec::new();
+    let mut paths = Vec::new();
+    for event in walk {
+        match event.unwrap() {
+            WalkEvent::EnterDir(dir) => {
+                open.push(dir.path.clone());
+                paths.push(format!("+{}", dir.path));
+            }
+            WalkEvent::LeaveDir(dir) => {
+                assert_eq!(open.pop().as_ref(), Some(&dir.path));
+                paths.push(format!("-{}", dir.path));
+            }
+            WalkEvent::File(file) => {
+                if let Some(parent) = open.last() {
+                    assert!(file.path.starts_with(parent.as_str()));
+                }
+                paths.push(file.path);
+            }
+        }
+    }
+    assert!(open.is_empty());
+    paths.sort();
+    paths
+}
+
+fn check_walk<F: FileManager>(fm: &F) {
+    write(fm, "root/a.txt", b"a");
+    write(fm, "root/one/b.txt", b"b");
+    write(fm, "root/one/c.dds", b"c");
+    write(fm, "root/one/two/d.txt", b"d");
+    fm.create_directory("root/empty").unwrap();
+
+    let all = [
+        "+root/empty",
+        "+root/one",
+        "+root/one/two",
+        "-root/empty",
+        "-root/one",
+        "-root/one/two",
+        "root/a.txt",
+        "root/one/b.txt",
+        "root/one/c.dds",
+        "root/one/two/d.txt",
+    ];
+    assert_eq!(walk_paths(fm.walk("root")), all);
+    assert_eq!(
+        walk_paths(fm.walk("root").max_depth(1)),
+        ["+root/empty", "+root/one", "-root/empty", "-root/one", "root/a.txt"]
+    );
+    assert!(walk_paths(fm.walk("root").max_depth(0)).is_empty());
+    assert_eq!(
+        walk_paths(fm.walk("root").prune(|dir| dir.name == "two")),
+        [
+            "+root/empty",
+            "+root/one",
+            "-root/empty",
+            "-root/one",
+            "root/a.txt",
+            "root/one/b.txt",
+            "root/one/c.dds"
+        ]
+    );
+    let files: Vec<_> = walk_paths(fm.walk("root").filter_name("*.txt"))
+        .into_iter()
+        .filter(|path| !path.starts_with(['+', '-']))
+        .collect();
+    assert_eq!(files, ["root/a.txt", "root/one/b.txt", "root/one/two/d.txt"]);
+    let files: Vec<_> = walk_paths(fm.walk("root").filter("*/*.TXT"))
+        .into_iter()
+        .filter(|path| !path.starts_with(['+', '-']))
+        .collect();
+    assert_eq!(files, ["root/one/b.txt"]);
+    assert!(matches!(fm.walk("missing").next(), Some(Err(Error::NotFound(_)))));
+}
+
+#[test]
+fn glob() {
+    check_glob(&new_archive());
+    check_glob(&MemoryArchive::new());
+    check_glob(&host_archive("glob"));
+}
+
+fn check_glob<F: FileManager>(fm: &F) {
+    write(fm, "media/a.txt", b"a");
+    write(fm, "media/one/b.txt", b"b");
+    write(fm, "media/one/c.dds", b"c");
+    write(fm, "media/one/two/d.txt", b"d");
+    let paths = |pattern| -> Vec<String> {
+        fm.glob(pattern).unwrap().into_iter().map(|e| e.path).collect()
+    };
+    assert_eq!(paths("media/**/*.txt"), ["media/a.txt", "media/one/b.txt", "media/one/two/d.txt"]);
+    assert_eq!(paths("media/*"), ["media/a.txt", "media/one"]);
+    assert_eq!(paths("/Media/one/*.dds"), ["/Media/one/c.dds"]);
+    assert_eq!(paths("media/one/two"), ["media/one/two"]);
+    assert_eq!(paths("**/t?o"), ["media/one/two"]);
+    assert!(paths("missing/**").is_empty());
+    assert!(paths("media/a.txt/*").is_empty());
+}
+
+#[test]
+fn delete() {
+    check_delete(&new_archive());
+    check_delete(&MemoryArchive::new());
+    check_delete(&host_archive("delete"));
+}
+
+fn check_delete<F: FileManager>(fm: &F) {
+    write(fm, "dir/a.txt", b"a");
+    assert!(matches!(fm.delete_directory("dir"), Err(Error::DirectoryNotEmpty(_))));
+    assert!(matches!(fm.delete_file("dir"), Err(Error::NotAFile(_))));
+    fm.delete_file("dir/A.txt").unwrap();
+    assert!(!fm.file_exists("dir/a.txt").unwrap());
+    fm.delete_directory("dir").unwrap();
+    assert!(!fm.file_exists("dir").unwrap());
+    write(fm, "dir/a.txt", b"again");
+    assert_eq!(read(fm, "dir/a.txt"), b"again");
+}
+
+#[test]
+fn import_export_round_trip() {
+    check_import_export(&new_archive(), "pk2");
+    check_import_export(&MemoryArchive::new(), "memory");
+    check_import_export(&host_archive("import-export"), "host");
+}
+
+fn check_import_export<F: FileManager>(fm: &F, name: &str) {
+    let src = temp_dir(&format!("import-{name}"));
+    std::fs::create_dir_all(src.join("client/sub")).unwrap();
+    std::fs::write(src.join("client/a.txt"), b"a").unwrap();
+    std::fs::write(src.join("client/sub/b.bin"), [1, 2, 3]).unwrap();
+
+    fm.import_directory(&src, "/", "client").unwrap();
+    assert_eq!(read(fm, "client/sub/b.bin"), [1, 2, 3]);
+
+    let dst = temp_dir(&format!("export-{name}"));
+    fm.export_directory("", &dst, "client").unwrap();
+    assert_eq!(std::fs::read(dst.join("client/a.txt")).unwrap(), b"a");
+    assert_eq!(std::fs::read(dst.join("client/sub/b.bin")).unwrap(), [1, 2, 3]);
+    std::fs::remove_dir_all(src).unwrap();
+    std::fs::remove_dir_all(dst).unwrap();
+}
+
+#[test]
+fn memory_archive_seed_and_inspect() {
+    let mut fm = MemoryArchive::from_files([
+        ("Media/type.txt", &b"Language = English"[..]),
+        ("Media/server_dep/silkroad/gateway.dat", &[1, 2]),
+    ])
+    .unwrap();
+    fm.change_directory("media").unwrap();
+    assert_eq!(read(&fm, "TYPE.TXT"), b"Language = English");
+    write(&fm, "new.txt", b"new");
+    fm.delete_file("server_dep/silkroad/gateway.dat").unwrap();
+
+    let files = fm.files();
+    assert_eq!(files.keys().collect::<Vec<_>>(), ["Media/new.txt", "Media/type.txt"]);
+    assert_eq!(files["Media/new.txt"], b"new");
+    assert_eq!(fm.read("/media/new.txt").unwrap(), b"new");
+    assert!(matches!(fm.create_file(&"x".repeat(81)), Err(Error::NameTooLong(_))));
+}
+
+#[test]
+fn host_file_manager_is_case_insensitive() {
+    let mut fm = host_archive("case");
+    let root = fm.virtual_path().to_owned();
+    std::fs::create_dir_all(root.join("Media/TextData")).unwrap();
+    std::fs::write(root.join("Media/TextData/Item.txt"), b"item").unwrap();
+    assert_eq!(read(&fm, "media/textdata/ITEM.TXT"), b"item");
+    write(&fm, "MEDIA/textdata/new.txt", b"new");
+    assert_eq!(std::fs::read(root.join("Media/TextData/new.txt")).unwrap(), b"new");
+    write(&fm, "../../outside.txt", b"clamped");
+    assert!(root.join("outside.txt").exists());
+
+    fm.set_virtual_path(root.join("Media")).unwrap();
+    assert_eq!(read(&fm, "textdata/item.txt"), b"item");
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn host_symlinks_stay_inside_the_root() {
+    use std::os::unix::fs::symlink;
+
+    let outside = temp_dir("host-outside");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+    let fm = host_archive("symlinks");
+    let root = fm.virtual_path().to_owned();
+    write(&fm, "inside/file.txt", b"inside");
+    symlink(&outside, root.join("out")).unwrap();
+    symlink(outside.join("secret.txt"), root.join("secret.txt")).unwrap();
+    symlink(outside.join("missing.txt"), root.join("dangling.txt")).unwrap();
+    symlink(root.join("inside"), root.join("alias")).unwrap();
+
+    assert!(matches!(fm.open_file("out/secret.txt"), Err(Error::InvalidPath(_))));
+    assert!(matches!(fm.open_file("SECRET.txt"), Err(Error::InvalidPath(_))));
+    assert!(matches!(fm.create_file("out/new.txt"), Err(Error::InvalidPath(_))));
+    assert!(matches!(fm.create_file("dangling.txt"), Err(Error::InvalidPath(_))));
+    assert!(matches!(fm.delete_file("secret.txt"), Err(Error::InvalidPath(_))));
+    assert!(!outside.join("new.txt").exists() && !outside.join("missing.txt").exists());
+    // links that stay inside the root are followed
+    assert_eq!(read(&fm, "alias/file.txt"), b"inside");
+
+    std::fs::remove_dir_all(outside).unwrap();
+    std::fs::remove_dir_all(root).unwrap();
+}
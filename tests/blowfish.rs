@@ -0,0 +1,73 @@
+use gfxfilemanager::pk2::blowfish::{derive_key, Blowfish, CHECKSUM_PLAINTEXT};
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::Error;
+
+/// Converts a reference test vector into the little-endian word order used by pk2.
+fn le_words(hex: u64) -> [u8; 8] {
+    let [a, b, c, d, e, f, g, h] = hex.to_be_bytes();
+    [d, c, b, a, h, g, f, e]
+}
+
+#[test]
+fn reference_vectors() {
+    // (key, plaintext, ciphertext) from Eric Young's blowfish test vectors
+    let vectors: [(u64, u64, u64); 4] = [
+        (0x0000000000000000, 0x0000000000000000, 0x4EF997456198DD78),
+        (0xFFFFFFFFFFFFFFFF, 0xFFFFFFFFFFFFFFFF, 0x51866FD5B85ECB8A),
+        (0x3000000000000000, 0x1000000000000001, 0x7D856F9A613063F2),
+        (0x0123456789ABCDEF, 0x1111111111111111, 0x61F9C3802281B096),
+    ];
+    for (key, plain, cipher) in vectors {
+        let blowfish = Blowfish::new(&key.to_be_bytes()).unwrap();
+        let mut buf = le_words(plain);
+        blowfish.encrypt(&mut buf).unwrap();
+        assert_eq!(buf, le_words(cipher), "key {key:016X}");
+        blowfish.decrypt(&mut buf).unwrap();
+        assert_eq!(buf, le_words(plain), "key {key:016X}");
+    }
+}
+
+#[test]
+fn default_password() {
+    assert_eq!(derive_key(DEFAULT_PASSWORD).unwrap(), [0x32, 0xCE, 0xDD, 0x7C, 0xBC, 0xA8]);
+
+    let blowfish = Blowfish::from_password(DEFAULT_PASSWORD).unwrap();
+    let mut buf = *CHECKSUM_PLAINTEXT;
+    blowfish.encrypt(&mut buf).unwrap();
+    assert_eq!(
+        buf,
+        [
+            0xD8, 0xDA, 0x30, 0xCF, 0x32, 0xE6, 0x71, 0xFC, 0xF8, 0x58, 0x15, 0x38, 0x9C, 0x47,
+            0x3A, 0xF7
+        ]
+    );
+    assert_eq!(blowfish.checksum()[..], [0xD8, 0xDA, 0x30, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(blowfish.verify_checksum(&blowfish.checksum()));
+    assert!(!Blowfish::from_password("169842").unwrap().verify_checksum(&blowfish.checksum()));
+}
+
+#[test]
+fn key_derivation() {
+    // the base key is only 10 bytes long, longer passwords are used as they are
+    let key = derive_key("abcdefghijkl").unwrap();
+    assert_eq!(key.len(), 12);
+    assert_eq!(key[10..], *b"kl");
+    assert_eq!(derive_key(&"x".repeat(100)).unwrap().len(), 56);
+    assert!(matches!(derive_key(""), Err(Error::InvalidPassword)));
+}
+
+#[test]
+fn round_trip_and_alignment() {
+    let blowfish = Blowfish::from_password("secret").unwrap();
+    let plain: Vec<u8> = (0..=255).collect();
+    let mut buf = plain.clone();
+    blowfish.encrypt(&mut buf).unwrap();
+    assert_ne!(buf, plain);
+    blowfish.decrypt(&mut buf).unwrap();
+    assert_eq!(buf, plain);
+
+    assert!(matches!(blowfish.encrypt(&mut [0; 12]), Err(Error::UnalignedBuffer(12))));
+    assert!(matches!(blowfish.decrypt(&mut [0; 7]), Err(Error::UnalignedBuffer(7))));
+    assert!(matches!(Blowfish::new(&[]), Err(Error::InvalidKeyLength(0))));
+    assert!(matches!(Blowfish::new(&[0; 57]), Err(Error::InvalidKeyLength(57))));
+}
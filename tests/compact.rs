@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::{FileManager, Pk2, WalkEvent};
+
+fn contents<B: std::io::Read + std::io::Seek>(archive: &Pk2<B>) -> BTreeMap<String, Vec<u8>>
+where
+    Pk2<B>: FileManager,
+{
+    archive
+        .walk("/")
+        .filter_map(|event| match event.unwrap() {
+            WalkEvent::File(file) => Some(file),
+            _ => None,
+        })
+        .map(|file| (file.path.to_ascii_lowercase(), archive.bytes(&file.path).unwrap()))
+        .collect()
+}
+
+fn compact(archive: &Pk2<Cursor<Vec<u8>>>) -> (Pk2<Cursor<Vec<u8>>>, u64) {
+    let mut out = Vec::new();
+    let summary = archive.compact_to(&mut out).unwrap();
+    assert_eq!(summary.compacted_len, out.len() as u64);
+    let compacted = Pk2::open_in(Cursor::new(out), DEFAULT_PASSWORD).unwrap();
+    (compacted, summary.reclaimed())
+}
+
+#[test]
+fn compact_reclaims_dead_space() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    for i in 0..45 {
+        let path = format!("Media/textdata/{i}.txt");
+        archive.create_file(&path).unwrap().write_all(&vec![i as u8; 100 + i]).unwrap();
+    }
+    // replacing with larger contents leaves the old payloads behind
+    for i in 0..10 {
+        let path = format!("Media/textdata/{i}.txt");
+        archive.create_file(&path).unwrap().write_all(&vec![0xff; 5000]).unwrap();
+    }
+    archive.create_file("Media/empty.txt").unwrap();
+    archive.create_directory("Music/nothing").unwrap();
+    archive.delete_directory_all("Media/textdata").unwrap();
+    archive.create_file("Media/textdata/new.txt").unwrap().write_all(b"new").unwrap();
+    archive.create_file("gone.txt").unwrap().write_all(&[1; 50_000]).unwrap();
+    archive.delete_file("gone.txt").unwrap();
+    for i in 0..30 {
+        archive.create_file(&format!("Data/{i}.bin")).unwrap().write_all(&[i; 1000]).unwrap();
+    }
+    let expected = contents(&archive);
+
+    let (compacted, reclaimed) = compact(&archive);
+    // everything that was released and not reused again is dropped
+    assert!(archive.free_bytes() > 20_000);
+    assert!(reclaimed >= archive.free_bytes(), "{reclaimed}");
+    assert_eq!(contents(&compacted), expected);
+    assert_eq!(compacted.read_dir("Music/nothing").unwrap().count(), 0);
+    let mut compacted = compacted;
+    compacted.change_directory("Data").unwrap();
+    assert_eq!(compacted.bytes("../Media/textdata/new.txt").unwrap(), b"new");
+
+    // the result is already as small as it gets and can be modified as usual
+    let (again, reclaimed) = compact(&compacted);
+    assert_eq!(reclaimed, 0);
+    again.create_file("Data/31.bin").unwrap().write_all(b"more").unwrap();
+    assert_eq!(again.bytes("Data/31.bin").unwrap(), b"more");
+    assert_eq!(again.bytes("Data/29.bin").unwrap(), [29; 1000]);
+}
+
+#[test]
+fn compact_to_new_file() {
+    let dir = std::env::temp_dir().join(format!("gfxfilemanager-compact-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let archive = Pk2::create(dir.join("src.pk2"), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"a").unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"aaaa").unwrap();
+
+    let summary = archive.compact(dir.join("dst.pk2")).unwrap();
+    assert_eq!(summary.files, 1);
+    assert_eq!(summary.directories, 0);
+    assert_eq!(summary.reclaimed(), 1);
+    assert_eq!(std::fs::metadata(dir.join("dst.pk2")).unwrap().len(), summary.compacted_len);
+    assert_eq!(
+        Pk2::open(dir.join("dst.pk2"), DEFAULT_PASSWORD).unwrap().bytes("a.txt").unwrap(),
+        b"aaaa"
+    );
+    // an existing file is never overwritten
+    assert!(archive.compact(dir.join("src.pk2")).is_err());
+
+    drop(archive);
+    std::fs::remove_dir_all(dir).unwrap();
+}
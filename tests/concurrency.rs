@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::{Error, Pk2};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn archives_are_send_and_sync() {
+    assert_send_sync::<Pk2<fs::File>>();
+    assert_send_sync::<Pk2<Cursor<Vec<u8>>>>();
+    #[cfg(feature = "mmap")]
+    assert_send_sync::<gfxfilemanager::pk2::MappedPk2>();
+}
+
+fn contents(i: usize) -> Vec<u8> {
+    (0..1000 + i * 37).map(|b| (b * 31 + i) as u8).collect()
+}
+
+#[test]
+fn parallel_readers() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    std::thread::scope(|s| {
+        for t in 0..4 {
+            let archive = &archive;
+            s.spawn(move || {
+                for i in (t..64).step_by(4) {
+                    let path = format!("data/{}/file{i}.bin", i % 3);
+                    archive.create_file(&path).unwrap().write_all(&contents(i)).unwrap();
+                }
+            });
+        }
+    });
+
+    std::thread::scope(|s| {
+        for t in 0..8 {
+            let archive = &archive;
+            s.spawn(move || {
+                for i in (0..64).map(|i| (i + t * 8) % 64) {
+                    let path = format!("data/{}/file{i}.bin", i % 3);
+                    let mut file = archive.open_file(&path).unwrap();
+                    let mut buf = Vec::new();
+                    file.read_to_end(&mut buf).unwrap();
+                    assert_eq!(buf, contents(i), "{path}");
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn positional_reads_keep_the_cursor() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"0123456789").unwrap();
+
+    let mut file = archive.open_file("a.txt").unwrap();
+    file.seek(SeekFrom::Start(2)).unwrap();
+    let mut buf = [0; 4];
+    assert_eq!(file.read_at(&mut buf, 6).unwrap(), 4);
+    assert_eq!(&buf, b"6789");
+    assert_eq!(file.read_at(&mut buf, 8).unwrap(), 2);
+    assert_eq!(&buf[..2], b"89");
+    assert_eq!(file.read_at(&mut buf, 10).unwrap(), 0);
+    assert_eq!(file.read_at(&mut buf, u64::MAX).unwrap(), 0);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"2345");
+}
+
+#[test]
+fn readers_of_a_container_file_while_writing() {
+    let path = std::env::temp_dir().join(format!("gfxfilemanager-conc-{}.pk2", std::process::id()));
+    let archive = Pk2::create(&path, DEFAULT_PASSWORD).unwrap();
+    for i in 0..16 {
+        archive.create_file(&format!("old/{i}.bin")).unwrap().write_all(&contents(i)).unwrap();
+    }
+    std::thread::scope(|s| {
+        let archive = &archive;
+        s.spawn(move || {
+            for i in 16..48 {
+                archive
+                    .create_file(&format!("new/{i}.bin"))
+                    .unwrap()
+                    .write_all(&contents(i))
+                    .unwrap();
+            }
+        });
+        for t in 0..4 {
+            s.spawn(move || {
+                for i in (0..64).map(|i| (i + t * 4) % 16) {
+                    assert_eq!(archive.bytes(&format!("old/{i}.bin")).unwrap(), contents(i));
+                }
+            });
+        }
+    });
+    drop(archive);
+
+    let archive = Pk2::open(&path, DEFAULT_PASSWORD).unwrap();
+    for i in 16..48 {
+        assert_eq!(archive.bytes(&format!("new/{i}.bin")).unwrap(), contents(i));
+    }
+    drop(archive);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn write_handles_look_up_their_entry_again() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    let mut file = archive.create_file("a.txt").unwrap();
+    file.write_all(b"late").unwrap();
+    // the slot of a.txt is taken by another file before the handle stores its contents
+    archive.delete_file("a.txt").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"b").unwrap();
+    assert!(matches!(file.flush().map_err(Error::from), Err(Error::NotFound(_))));
+    drop(file);
+    assert_eq!(archive.bytes("b.txt").unwrap(), b"b");
+
+    let mut file = archive.create_file("c.txt").unwrap();
+    file.write_all(b"c").unwrap();
+    archive.rename("c.txt", "d.txt").unwrap();
+    assert!(file.flush().is_err());
+    drop(file);
+    assert_eq!(archive.bytes("d.txt").unwrap(), b"");
+
+    // a file replaced in the meantime is overwritten as a whole
+    let mut file = archive.create_file("b.txt").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"replaced").unwrap();
+    file.write_all(b"mine").unwrap();
+    drop(file);
+    assert_eq!(archive.bytes("b.txt").unwrap(), b"mine");
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use gfxfilemanager::backend::ExtractAction;
+use gfxfilemanager::pk2::{FileTime, DEFAULT_PASSWORD};
+use gfxfilemanager::{Error, FileManager, MemoryArchive, Overwrite, Pk2};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("gfxfilemanager-extract-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn new_archive() -> Pk2<Cursor<Vec<u8>>> {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    for (path, data) in [
+        ("Media/textdata/item.txt", &b"item"[..]),
+        ("Media/textdata/skill.txt", b"skill data"),
+        ("Media/textdata/old/item.txt", b"old"),
+        ("Media/icon/item.ddj", b"icon"),
+        ("Media/readme.md", b"readme"),
+    ] {
+        archive.create_file(path).unwrap().write_all(data).unwrap();
+    }
+    archive.create_directory("Media/empty").unwrap();
+    archive
+}
+
+#[test]
+fn extract_tree() {
+    let archive = new_archive();
+    let dir = temp_dir("tree");
+    let mut events = Vec::new();
+    let summary = archive
+        .extract("media", &dir)
+        .progress(|p| events.push((p.entry.name.clone(), p.action, p.files, p.bytes)))
+        .run()
+        .unwrap();
+    assert_eq!(summary.written.len(), 5);
+    assert!(summary.skipped.is_empty());
+    assert_eq!(summary.bytes, 27);
+    // the target, textdata, old, icon and empty
+    assert_eq!(summary.directories, 5);
+    assert_eq!(fs::read(dir.join("textdata/old/item.txt")).unwrap(), b"old");
+    assert_eq!(fs::read(dir.join("icon/item.ddj")).unwrap(), b"icon");
+    assert!(dir.join("empty").is_dir());
+    assert_eq!(events.len(), 5);
+    assert_eq!(events.last().unwrap().2, 5);
+    assert_eq!(events.last().unwrap().3, 27);
+    assert!(events.iter().all(|e| e.1 == ExtractAction::Created));
+
+    // the timestamps of the entries are kept
+    let entry = archive.find_files("Media/readme.md").unwrap().remove(0);
+    let modified = fs::metadata(dir.join("readme.md")).unwrap().modified().unwrap();
+    let expected = entry.modify_time.to_system_time().unwrap();
+    let diff = modified.duration_since(expected).unwrap_or_else(|e| e.duration());
+    assert!(diff < Duration::from_millis(1), "{modified:?} != {expected:?}");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn filters() {
+    let archive = new_archive();
+    let dir = temp_dir("filters");
+    let summary = archive
+        .extract("/Media", &dir)
+        .include("**/*.txt")
+        .include("*.md")
+        .exclude("textdata/old")
+        .exclude("**/skill.*")
+        .run()
+        .unwrap();
+    let mut written = summary.written.clone();
+    written.sort();
+    assert_eq!(written, ["/Media/readme.md", "/Media/textdata/item.txt"]);
+    assert!(dir.join("textdata/item.txt").is_file());
+    // with include patterns only the directories of extracted files are created
+    assert!(!dir.join("icon").exists());
+    assert!(!dir.join("empty").exists());
+    assert!(!dir.join("textdata/old").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn overwrite_policies() {
+    let archive = new_archive();
+    let dir = temp_dir("overwrite");
+    fs::create_dir_all(dir.join("textdata")).unwrap();
+    fs::write(dir.join("textdata/item.txt"), "host").unwrap();
+    let run = |overwrite| {
+        archive.extract("Media/textdata", dir.join("textdata")).overwrite(overwrite).run()
+    };
+
+    let summary = run(Overwrite::Never).unwrap();
+    assert_eq!(summary.skipped, ["Media/textdata/item.txt"]);
+    assert_eq!(fs::read(dir.join("textdata/item.txt")).unwrap(), b"host");
+
+    assert!(matches!(run(Overwrite::Error), Err(Error::AlreadyExists(_))));
+    // the conflict is found before anything is written
+    let fresh = temp_dir("overwrite-error");
+    fs::create_dir_all(fresh.join("textdata/old")).unwrap();
+    fs::write(fresh.join("textdata/old/item.txt"), "host").unwrap();
+    let result = archive.extract("Media", &fresh).overwrite(Overwrite::Error).run();
+    assert!(matches!(result, Err(Error::AlreadyExists(_))));
+    for written in ["readme.md", "icon", "empty", "textdata/item.txt", "textdata/skill.txt"] {
+        assert!(!fresh.join(written).exists(), "{written}");
+    }
+    fs::remove_dir_all(fresh).unwrap();
+
+    // the host file was modified before the entries
+    let old = fs::File::options().write(true).open(dir.join("textdata/item.txt")).unwrap();
+    old.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)).unwrap();
+    drop(old);
+    let summary = run(Overwrite::IfNewer).unwrap();
+    assert_eq!(summary.written, ["Media/textdata/item.txt"]);
+    assert_eq!(summary.skipped.len(), 2);
+    assert_eq!(fs::read(dir.join("textdata/item.txt")).unwrap(), b"item");
+    // now the files carry the timestamps of the entries
+    let summary = run(Overwrite::IfNewer).unwrap();
+    assert!(summary.written.is_empty());
+    assert_eq!(summary.skipped.len(), 3);
+
+    let summary = run(Overwrite::Always).unwrap();
+    assert_eq!(summary.written.len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn other_backends() {
+    let archive = MemoryArchive::from_files([("a/b.txt", "b")]).unwrap();
+    let dir = temp_dir("names");
+    let summary = archive.extract("a", &dir).preserve_times(false).run().unwrap();
+    assert_eq!(summary.written, ["a/b.txt"]);
+    assert!(matches!(archive.extract("missing", &dir).run(), Err(Error::NotFound(_))));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn names_with_drive_prefixes_are_rejected() {
+    let dir = temp_dir("prefix");
+    for name in ["C:x", "C:", "a:b.txt"] {
+        let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+        archive.create_file("ok.txt").unwrap().write_all(b"ok").unwrap();
+        archive.create_file(&format!("sub/{name}")).unwrap().write_all(b"escape").unwrap();
+        let result = archive.extract("/", &dir).run();
+        assert!(matches!(result, Err(Error::InvalidPath(ref n)) if n == name), "{result:?}");
+        assert!(!dir.exists());
+    }
+}
+
+#[test]
+fn garbage_times_are_left_unset() {
+    assert_eq!(FileTime(u64::MAX).to_system_time(), None);
+    assert_eq!(FileTime(116_444_736_000_000_000).to_system_time(), Some(SystemTime::UNIX_EPOCH));
+
+    let archive = new_archive();
+    let mut file = archive.create_file("Media/readme.md").unwrap();
+    file.write_all(b"readme").unwrap();
+    file.set_file_time(FileTime(u64::MAX), FileTime(u64::MAX));
+    drop(file);
+    let dir = temp_dir("times");
+    archive.extract("Media", &dir).run().unwrap();
+    assert_eq!(fs::read(dir.join("readme.md")).unwrap(), b"readme");
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,87 @@
+use gfxfilemanager::Glob;
+
+fn is_match(pattern: &str, path: &str) -> bool {
+    Glob::new(pattern).is_match(path)
+}
+
+#[test]
+fn wildcards() {
+    assert!(is_match("*", "item.txt"));
+    assert!(is_match("item?.txt", "item1.txt"));
+    assert!(!is_match("item?.txt", "item.txt"));
+    assert!(!is_match("item?.txt", "item12.txt"));
+    assert!(is_match("i*m*.txt", "item_main.txt"));
+    assert!(is_match("***.txt", "a.txt"));
+    assert!(is_match("ITEM.TXT", "item.txt"));
+    assert!(is_match("?", "ß"));
+    assert!(!is_match("ß", "SS"));
+}
+
+#[test]
+fn multiple_dots() {
+    assert!(is_match("*.txt", "a.b.txt"));
+    assert!(is_match("*.b.txt", "a.b.txt"));
+    assert!(is_match("a.*", "a.b.txt"));
+    assert!(!is_match("*.b", "a.b.txt"));
+    assert!(!is_match("*.txt", "a.txt.bak"));
+    assert!(is_match("*.*.*", "a.b.c"));
+    assert!(!is_match("?.txt", "..txt."));
+    assert!(is_match("*.", "a."));
+    assert!(!is_match("*.", "a"));
+}
+
+#[test]
+fn trailing_dot_star() {
+    assert!(is_match("*.*", "readme"));
+    assert!(is_match("*.*", "a.b"));
+    assert!(is_match("item.*", "item"));
+    assert!(is_match("item.*", "item.ddj"));
+    assert!(!is_match("item.*", "items"));
+    assert!(!is_match("*.?", "readme"));
+}
+
+#[test]
+fn separators_and_components() {
+    assert!(is_match("media/*.txt", "Media\\item.txt"));
+    assert!(is_match("/media//*.txt", "media/item.txt/"));
+    assert!(!is_match("media/*.txt", "media/textdata/item.txt"));
+    assert!(!is_match("*", "media/item.txt"));
+    assert!(!is_match("media/*", "media"));
+}
+
+#[test]
+fn recursive() {
+    assert!(is_match("**", ""));
+    assert!(is_match("**", "a/b/c"));
+    assert!(is_match("**/*.txt", "item.txt"));
+    assert!(is_match("**/*.txt", "a/b/item.txt"));
+    assert!(is_match("media/**/item.txt", "media/item.txt"));
+    assert!(is_match("media/**/item.txt", "media/a/b/item.txt"));
+    assert!(!is_match("media/**/item.txt", "other/media/item.txt"));
+    assert!(is_match("a/**/b/**/c", "a/b/x/b/c"));
+    assert!(is_match("a/**/**/c", "a/c"));
+    // `**` is only recursive as a whole component
+    assert!(is_match("a**", "abc"));
+    assert!(!is_match("a**", "a/b"));
+}
+
+#[test]
+fn prefixes() {
+    let glob = Glob::new("/media/textdata/*.txt");
+    assert_eq!(glob.literal_prefix(), "/media/textdata");
+    assert!(!glob.is_literal());
+    assert!(glob.could_match_below("media"));
+    assert!(glob.could_match_below("media/textdata"));
+    assert!(!glob.could_match_below("media/icon"));
+    assert!(!glob.could_match_below("media/textdata/item.txt"));
+
+    let glob = Glob::new("media\\**\\*.ddj");
+    assert_eq!(glob.literal_prefix(), "media");
+    assert!(glob.could_match_below("media/a/b/c"));
+    assert!(!glob.could_match_below("music"));
+
+    let glob = Glob::new("Media/Item.txt");
+    assert!(glob.is_literal());
+    assert_eq!(glob.literal_prefix(), "Media/Item.txt");
+    assert_eq!(glob.to_string(), "Media/Item.txt");
+}
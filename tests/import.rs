@@ -0,0 +1,192 @@
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use gfxfilemanager::pk2::{FileTime, ImportAction, DEFAULT_PASSWORD};
+use gfxfilemanager::{Error, Pk2};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("gfxfilemanager-import-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn host_tree(name: &str) -> PathBuf {
+    let dir = temp_dir(name);
+    fs::create_dir_all(dir.join("textdata/old")).unwrap();
+    fs::create_dir_all(dir.join("empty")).unwrap();
+    fs::write(dir.join("textdata/item.txt"), "item").unwrap();
+    fs::write(dir.join("textdata/skill.txt"), "skill data").unwrap();
+    fs::write(dir.join("textdata/old/item.txt"), "old").unwrap();
+    fs::write(dir.join("readme.md"), "readme").unwrap();
+    dir
+}
+
+fn set_modified(path: &Path, time: SystemTime) {
+    fs::File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+}
+
+fn actions(plan: &gfxfilemanager::pk2::ImportPlan) -> Vec<(&str, ImportAction)> {
+    plan.steps.iter().map(|step| (step.target.as_str(), step.action)).collect()
+}
+
+#[test]
+fn plan_and_apply() {
+    let dir = host_tree("apply");
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    set_modified(&dir.join("readme.md"), mtime);
+
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_directory("Media").unwrap();
+    let plan = archive.import(&dir, "Media").dry_run(true).run().unwrap();
+    assert_eq!(
+        actions(&plan),
+        [
+            ("Media/empty", ImportAction::CreateDirectory),
+            ("Media/readme.md", ImportAction::Create),
+            ("Media/textdata", ImportAction::CreateDirectory),
+            ("Media/textdata/item.txt", ImportAction::Create),
+            ("Media/textdata/old", ImportAction::CreateDirectory),
+            ("Media/textdata/old/item.txt", ImportAction::Create),
+            ("Media/textdata/skill.txt", ImportAction::Create),
+        ]
+    );
+    assert_eq!(plan.bytes(), 23);
+    // a dry run leaves the archive alone
+    assert!(!archive.file_exists("Media/readme.md").unwrap());
+
+    let mut progress = Vec::new();
+    let applied = archive
+        .import(&dir, "Media")
+        .progress(|p| progress.push((p.steps, p.total_steps, p.bytes, p.total_bytes)))
+        .run()
+        .unwrap();
+    assert_eq!(applied, plan);
+    assert_eq!(progress.last(), Some(&(7, 7, 23, 23)));
+    assert_eq!(archive.bytes("media/textdata/old/item.txt").unwrap(), b"old");
+    assert_eq!(archive.read_dir("Media/empty").unwrap().count(), 0);
+    let readme = archive.find_files("Media/readme.md").unwrap().remove(0);
+    assert_eq!(readme.modify_time, FileTime::from(mtime));
+
+    // only what changed on the host is written again
+    fs::write(dir.join("textdata/item.txt"), "ITEM").unwrap();
+    // the same size with a new modification time makes the contents get compared
+    set_modified(&dir.join("textdata/item.txt"), SystemTime::now() + Duration::from_secs(60));
+    fs::write(dir.join("textdata/skill.txt"), "more skill data").unwrap();
+    fs::write(dir.join("new.txt"), "new").unwrap();
+    let plan = archive.import(&dir, "/MEDIA").run().unwrap();
+    let changed: Vec<_> = plan
+        .steps
+        .iter()
+        .filter(|s| s.action != ImportAction::Unchanged)
+        .map(|s| (s.target.as_str(), s.action))
+        .collect();
+    assert_eq!(
+        changed,
+        [
+            ("/MEDIA/new.txt", ImportAction::Create),
+            ("/MEDIA/textdata/item.txt", ImportAction::Replace),
+            ("/MEDIA/textdata/skill.txt", ImportAction::Replace),
+        ]
+    );
+    assert_eq!(plan.filter(ImportAction::Unchanged).count(), 2);
+    assert_eq!(archive.bytes("Media/textdata/item.txt").unwrap(), b"ITEM");
+    assert_eq!(archive.bytes("Media/textdata/skill.txt").unwrap(), b"more skill data");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn mappings_and_conflicts() {
+    let dir = host_tree("mappings");
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    let plan = archive
+        .import(dir.join("readme.md"), "docs/README.md")
+        .add(dir.join("textdata/old"), "legacy")
+        .preserve_times(false)
+        .run()
+        .unwrap();
+    assert_eq!(
+        actions(&plan),
+        [
+            ("docs/README.md", ImportAction::Create),
+            ("legacy", ImportAction::CreateDirectory),
+            ("legacy/item.txt", ImportAction::Create),
+        ]
+    );
+    assert_eq!(archive.bytes("docs/readme.md").unwrap(), b"readme");
+    assert_eq!(archive.bytes("legacy/item.txt").unwrap(), b"old");
+
+    // a host directory where the archive has a file and the other way around
+    assert!(matches!(
+        archive.import(dir.join("textdata"), "docs/readme.md").plan(),
+        Err(Error::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        archive.import(dir.join("readme.md"), "legacy").plan(),
+        Err(Error::AlreadyExists(_))
+    ));
+
+    fs::write(dir.join(format!("{}.txt", "x".repeat(90))), "long").unwrap();
+    assert!(matches!(archive.import(&dir, "/").plan(), Err(Error::NameTooLong(_))));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn trusted_mtimes_skip_the_comparison() {
+    let dir = host_tree("times");
+    let item = dir.join("textdata/item.txt");
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    set_modified(&item, mtime);
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.import(&dir, "/").run().unwrap();
+
+    // rewritten with the old time restored, the contents still decide by default
+    fs::write(&item, "ITEM").unwrap();
+    set_modified(&item, mtime);
+    let plan = archive.import(&item, "textdata/item.txt").plan().unwrap();
+    assert_eq!(actions(&plan), [("textdata/item.txt", ImportAction::Replace)]);
+
+    // unless the modification time is trusted
+    let plan = archive.import(&item, "textdata/item.txt").trust_mtime(true).run().unwrap();
+    assert_eq!(actions(&plan), [("textdata/item.txt", ImportAction::Unchanged)]);
+    assert_eq!(archive.bytes("textdata/item.txt").unwrap(), b"item");
+
+    // with another time the contents decide either way
+    set_modified(&item, mtime + Duration::from_secs(1));
+    let plan = archive.import(&item, "textdata/item.txt").trust_mtime(true).run().unwrap();
+    assert_eq!(actions(&plan), [("textdata/item.txt", ImportAction::Replace)]);
+    assert_eq!(archive.bytes("textdata/item.txt").unwrap(), b"ITEM");
+    // the entry has a time of its own when times are not preserved
+    fs::write(dir.join("readme.md"), "README").unwrap();
+    archive.import(&dir, "/").preserve_times(false).run().unwrap();
+    let plan = archive.import(&dir, "/").plan().unwrap();
+    assert!(plan.steps.iter().all(|step| step.action != ImportAction::Replace));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn large_files_are_streamed() {
+    let dir = temp_dir("large");
+    fs::create_dir_all(&dir).unwrap();
+    let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(dir.join("big.bin"), &data).unwrap();
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("big.bin").unwrap().write_all(b"small").unwrap();
+
+    let plan = archive.import(dir.join("big.bin"), "big.bin").run().unwrap();
+    assert_eq!(actions(&plan), [("big.bin", ImportAction::Replace)]);
+    assert_eq!(archive.bytes("big.bin").unwrap(), data);
+
+    // a smaller file reuses the space of the payload in place
+    fs::write(dir.join("big.bin"), &data[..100_000]).unwrap();
+    archive.import(dir.join("big.bin"), "big.bin").run().unwrap();
+    assert_eq!(archive.bytes("big.bin").unwrap(), &data[..100_000]);
+    assert!(archive.free_bytes() >= 200_000);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,104 @@
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use gfxfilemanager::backend::ContainerStamp;
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::{Error, FileManager, Index, Pk2};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("gfxfilemanager-index-{name}-{}", std::process::id()))
+}
+
+fn fill<F: FileManager>(fm: &F) {
+    for (path, data) in [
+        ("Media/textdata/item.txt", &b"item"[..]),
+        ("Media/textdata/skill.txt", b"skill data"),
+        ("Media/icon/item.ddj", b"icon"),
+        ("Media/empty.txt", b""),
+        ("readme.txt", b"hello"),
+    ] {
+        fm.create_file(path).unwrap().write_all(data).unwrap();
+    }
+    fm.create_directory("Music").unwrap();
+}
+
+#[test]
+fn lookup_and_navigation() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    fill(&archive);
+    let index = Index::build(&archive).unwrap();
+    assert_eq!(index.len(), 10);
+
+    let item = index.lookup("/media\\TEXTDATA/./item.txt").unwrap();
+    assert_eq!(index[item].name, "item.txt");
+    assert_eq!(index[item].size, 4);
+    assert!(index[item].is_file());
+    assert!(index[item].position.is_some());
+    assert_eq!(index.path(item), "Media/textdata/item.txt");
+    assert_eq!(index.dir_entry(item).path, "Media/textdata/item.txt");
+
+    let textdata = index[item].parent().unwrap();
+    assert_eq!(index.path(textdata), "Media/textdata");
+    assert_eq!(index[textdata].total_size(), 14);
+    let media = index[textdata].parent().unwrap();
+    assert_eq!(index[media].total_size(), 18);
+    assert_eq!(index[media].children().len(), 3);
+    assert_eq!(index[index.root()].total_size(), 23);
+    assert_eq!(index[index.root()].parent(), None);
+    assert_eq!(index.lookup(""), Some(index.root()));
+    assert_eq!(index.get("music").unwrap().total_size(), 0);
+
+    assert!(index.lookup("media/missing").is_none());
+    assert!(index.lookup("media/textdata/item.txt/x").is_none());
+    assert_eq!(index.iter().filter(|(_, entry)| entry.is_file()).count(), 5);
+}
+
+#[test]
+fn serialization_round_trip() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    fill(&archive);
+    let mut index = Index::build(&archive).unwrap();
+    index.set_stamp(Some(ContainerStamp { len: 1234, modified: Default::default() }));
+
+    let mut buf = Vec::new();
+    index.write_to(&mut buf).unwrap();
+    let loaded = Index::read_from(buf.as_slice()).unwrap();
+    assert_eq!(loaded.stamp(), index.stamp());
+    assert_eq!(loaded.len(), index.len());
+    for (id, entry) in index.iter() {
+        assert_eq!(&loaded[id], entry);
+        assert_eq!(loaded.lookup(&index.path(id)), Some(id));
+    }
+
+    assert!(matches!(Index::read_from(&buf[..buf.len() - 1]), Err(Error::InvalidIndex(_))));
+    buf[0] = b'X';
+    assert!(matches!(Index::read_from(buf.as_slice()), Err(Error::InvalidIndex(_))));
+}
+
+#[test]
+fn cache_is_reused_until_the_container_changes() {
+    let container = temp_path("container.pk2");
+    let cache = temp_path("container.idx");
+    let _ = std::fs::remove_file(&cache);
+    fill(&Pk2::create(&container, DEFAULT_PASSWORD).unwrap());
+
+    let open = || Pk2::open(&container, DEFAULT_PASSWORD);
+    let index = Index::cached(&cache, &container, open).unwrap();
+    assert_eq!(index.stamp(), Some(ContainerStamp::of(&container).unwrap()));
+    assert!(index.get("readme.txt").is_some());
+
+    let unused = || -> Result<Pk2, Error> { panic!("the cache should have been used") };
+    let cached = Index::cached(&cache, &container, unused).unwrap();
+    assert_eq!(cached.len(), index.len());
+
+    let file = std::fs::OpenOptions::new().read(true).write(true).open(&container).unwrap();
+    let archive = Pk2::open_in(file, DEFAULT_PASSWORD).unwrap();
+    archive.create_file("new.txt").unwrap().write_all(&[0; 5000]).unwrap();
+    drop(archive);
+    let rebuilt = Index::cached(&cache, &container, open).unwrap();
+    assert_eq!(rebuilt.get("new.txt").unwrap().size, 5000);
+    assert_eq!(Index::load(&cache).unwrap().len(), rebuilt.len());
+
+    std::fs::remove_file(container).unwrap();
+    std::fs::remove_file(cache).unwrap();
+}
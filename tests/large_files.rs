@@ -0,0 +1,76 @@
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use std::io::ErrorKind;
+
+use gfxfilemanager::{Error, Pk2};
+
+const FAR: u64 = 5 << 30;
+
+#[test]
+fn payloads_beyond_4_gib() {
+    let path =
+        std::env::temp_dir().join(format!("gfxfilemanager-large-{}.pk2", std::process::id()));
+    let archive = Pk2::create(&path, DEFAULT_PASSWORD).unwrap();
+    archive.create_directory("media").unwrap();
+    // grow the container sparsely, so the next payload is appended past the 32-bit range
+    OpenOptions::new().write(true).open(&path).unwrap().set_len(FAR).unwrap();
+    archive.create_file("media/far.bin").unwrap().write_all(b"0123456789").unwrap();
+    drop(archive);
+
+    let archive = Pk2::open(&path, DEFAULT_PASSWORD).unwrap();
+    let entry = archive.read_dir("media").unwrap().next().unwrap().unwrap();
+    assert_eq!(entry.position, Some(FAR));
+    assert_eq!(archive.bytes("media/far.bin").unwrap(), b"0123456789");
+
+    let mut file = archive.open_file("media/far.bin").unwrap();
+    assert_eq!(file.seek(SeekFrom::End(-4)).unwrap(), 6);
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, "6789");
+    drop(file);
+
+    #[cfg(feature = "mmap")]
+    {
+        let mapped = gfxfilemanager::pk2::MappedPk2::open(&path, DEFAULT_PASSWORD).unwrap();
+        assert_eq!(mapped.bytes("media/far.bin").unwrap(), b"0123456789");
+    }
+    drop(archive);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn out_of_range_seeks() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.bin").unwrap().write_all(&[1; 16]).unwrap();
+
+    let mut file = archive.open_file("a.bin").unwrap();
+    assert!(file.seek(SeekFrom::Current(-1)).is_err());
+    assert!(file.seek(SeekFrom::End(i64::MIN)).is_err());
+    // seeking past the end is allowed like for std files and reads nothing
+    assert_eq!(file.seek(SeekFrom::Start(u64::MAX)).unwrap(), u64::MAX);
+    assert_eq!(file.read(&mut [0; 4]).unwrap(), 0);
+    assert!(file.seek(SeekFrom::Current(1)).is_err());
+    assert_eq!(file.stream_position().unwrap(), u64::MAX);
+    drop(file);
+
+    let mut file = archive.create_file("b.bin").unwrap();
+    assert_eq!(file.seek(SeekFrom::Start(u64::from(u32::MAX))).unwrap(), u64::from(u32::MAX));
+    let err = file.seek(SeekFrom::Current(1)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+    assert!(file.seek(SeekFrom::Start(1 << 32)).is_err());
+    assert!(file.seek(SeekFrom::End(-1)).is_err());
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.write_all(b"ok").unwrap();
+    drop(file);
+    assert_eq!(archive.bytes("b.bin").unwrap(), b"ok");
+}
+
+#[test]
+fn seek_errors_convert_back_to_the_typed_error() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    let mut file = archive.create_file("a.bin").unwrap();
+    let err = file.seek(SeekFrom::Start(1 << 32)).unwrap_err();
+    assert!(matches!(Error::from(err), Error::FileTooLarge(_)));
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "mmap")]
+
+use std::io::Write;
+
+use gfxfilemanager::pk2::{MappedPk2, DEFAULT_PASSWORD};
+use gfxfilemanager::{Error, FileManager, Index, Pk2};
+
+#[test]
+fn zero_copy_reads() {
+    let path = std::env::temp_dir().join(format!("gfxfilemanager-mmap-{}.pk2", std::process::id()));
+    let archive = Pk2::create(&path, DEFAULT_PASSWORD).unwrap();
+    archive.create_file("Media/textdata/item.txt").unwrap().write_all(b"item").unwrap();
+    let big: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+    archive.create_file("Media/big.bin").unwrap().write_all(&big).unwrap();
+    archive.create_file("empty.txt").unwrap();
+    drop(archive);
+
+    let mut mapped = MappedPk2::open(&path, DEFAULT_PASSWORD).unwrap();
+    assert_eq!(mapped.bytes("media/TEXTDATA/item.txt").unwrap(), b"item");
+    assert_eq!(mapped.bytes("empty.txt").unwrap(), b"");
+    let slice = mapped.bytes("Media\\big.bin").unwrap();
+    assert_eq!(slice, big.as_slice());
+    let container = mapped.as_bytes().as_ptr_range();
+    assert!(container.contains(&slice.as_ptr()));
+
+    assert!(matches!(mapped.bytes("media"), Err(Error::NotAFile(_))));
+    assert!(matches!(mapped.bytes("missing"), Err(Error::NotFound(_))));
+
+    // everything else goes through the regular archive
+    mapped.change_directory("media").unwrap();
+    assert_eq!(mapped.bytes("textdata/item.txt").unwrap(), b"item");
+    assert_eq!(FileManager::bytes(&*mapped, "big.bin").unwrap(), big);
+    assert_eq!(Index::build(&*mapped).unwrap().len(), 6);
+    assert_eq!(mapped.read_dir("/").unwrap().count(), 2);
+    assert!(mapped.create_file("new.txt").is_err());
+
+    drop(mapped);
+    std::fs::remove_file(path).unwrap();
+}
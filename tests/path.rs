@@ -0,0 +1,59 @@
+use std::collections::HashSet;
+
+use gfxfilemanager::pk2::MAX_NAME_LEN;
+use gfxfilemanager::{ArchivePath, Error, FileManager, MemoryArchive};
+
+#[test]
+fn normalizes() {
+    let path = ArchivePath::new("\\Media//textdata\\.\\item.txt\\").unwrap();
+    assert_eq!(path.components(), ["Media", "textdata", "item.txt"]);
+    assert_eq!(path.to_string(), "Media/textdata/item.txt");
+    assert_eq!(path.to_dll_path(), "\\Media\\textdata\\item.txt");
+    assert_eq!(path.name(), Some("item.txt"));
+    assert_eq!(path.parent().unwrap().to_string(), "Media/textdata");
+
+    let root = ArchivePath::new("/./").unwrap();
+    assert!(root.is_root());
+    assert_eq!(root.to_string(), "");
+    assert!(root.parent().is_none());
+    assert_eq!(ArchivePath::new("../../a").unwrap().to_string(), "a");
+}
+
+#[test]
+fn resolves_against_directory() {
+    let cwd = ArchivePath::new("media/textdata").unwrap();
+    assert_eq!(cwd.resolve("item.txt").unwrap().to_string(), "media/textdata/item.txt");
+    assert_eq!(cwd.resolve("..\\icon").unwrap().to_string(), "media/icon");
+    assert_eq!(cwd.resolve("/music").unwrap().to_string(), "music");
+    assert_eq!(cwd.join("/sub/a").unwrap().to_string(), "media/textdata/sub/a");
+    assert!(cwd.resolve("a.txt").unwrap().starts_with(&ArchivePath::new("MEDIA").unwrap()));
+    assert!(!cwd.starts_with(&ArchivePath::new("media/text").unwrap()));
+}
+
+#[test]
+fn compares_case_insensitively() {
+    let a = ArchivePath::new("Media/Item.TXT").unwrap();
+    let b = ArchivePath::new("media\\item.txt").unwrap();
+    assert_eq!(a, b);
+    assert_eq!(a.to_string(), "Media/Item.TXT");
+    assert_eq!(HashSet::from([a.clone(), b]).len(), 1);
+    assert!(a < ArchivePath::new("media/item.txt/x").unwrap());
+    assert_ne!(a, ArchivePath::new("media/item.txt/x").unwrap());
+}
+
+#[test]
+fn enforces_name_limit() {
+    let longest = "a".repeat(MAX_NAME_LEN);
+    let too_long = "a".repeat(MAX_NAME_LEN + 1);
+    assert!(ArchivePath::new(&format!("dir/{longest}")).is_ok());
+    assert!(matches!(
+        ArchivePath::new(&format!("dir/{too_long}/x")),
+        Err(Error::NameTooLong(name)) if name == too_long
+    ));
+    assert!(matches!(ArchivePath::new("a\0b"), Err(Error::InvalidPath(_))));
+
+    let archive = MemoryArchive::new();
+    assert!(archive.create_file(&format!("dir/{longest}")).is_ok());
+    assert!(matches!(archive.create_file(&format!("dir/{too_long}")), Err(Error::NameTooLong(_))));
+    assert!(matches!(archive.file_exists(&too_long), Err(Error::NameTooLong(_))));
+}
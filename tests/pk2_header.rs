@@ -0,0 +1,50 @@
+use std::io::Cursor;
+
+use gfxfilemanager::pk2::blowfish::Blowfish;
+use gfxfilemanager::pk2::{Pk2Header, DEFAULT_PASSWORD, HEADER_SIZE};
+use gfxfilemanager::{Error, Pk2};
+
+#[test]
+fn layout() {
+    let header = Pk2Header::new(&Blowfish::from_password(DEFAULT_PASSWORD).unwrap());
+    let bytes = header.to_bytes();
+    assert_eq!(&bytes[..21], b"JoyMax File Manager!\n");
+    assert!(bytes[21..30].iter().all(|&b| b == 0));
+    assert_eq!(bytes[30..34], [0x02, 0x00, 0x00, 0x01]);
+    assert_eq!(bytes[34], 1);
+    assert_eq!(bytes[35..38], [0xD8, 0xDA, 0x30]);
+    assert!(bytes[38..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn round_trip() {
+    let mut header = Pk2Header::unencrypted();
+    header.reserved[0] = 0xAB;
+    header.version = 0x0100_0001;
+    let mut buf = Vec::new();
+    header.write_to(&mut buf).unwrap();
+    assert_eq!(buf.len(), HEADER_SIZE);
+    assert_eq!(Pk2Header::read_from(buf.as_slice()).unwrap(), header);
+}
+
+#[test]
+fn check_password() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), "secret").unwrap();
+    let data = archive.into_inner().into_inner();
+    let header = Pk2Header::read_from(data.as_slice()).unwrap();
+    assert!(header.encrypted);
+    assert!(header.check_password("secret").unwrap().is_some());
+    assert!(matches!(header.check_password(DEFAULT_PASSWORD), Err(Error::WrongPassword)));
+    assert!(matches!(header.check_password(""), Err(Error::InvalidPassword)));
+
+    let plain = Pk2Header::unencrypted();
+    assert!(plain.check_password("anything").unwrap().is_none());
+}
+
+#[test]
+fn rejects_foreign_files() {
+    let mut bytes = Pk2Header::unencrypted().to_bytes();
+    bytes[0] = b'j';
+    assert!(matches!(Pk2Header::parse(&bytes), Err(Error::InvalidSignature)));
+    assert!(matches!(Pk2Header::read_from(&b"JoyMax"[..]), Err(Error::InvalidSignature)));
+}
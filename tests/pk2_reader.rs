@@ -0,0 +1,143 @@
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+use gfxfilemanager::{Error, Pk2};
+
+const BLOCK_SIZE: usize = 2560;
+
+/// Writes an unencrypted container by hand so the reader is tested independently of the writer.
+struct RawArchive {
+    buf: Vec<u8>,
+}
+
+impl RawArchive {
+    fn new() -> Self {
+        let mut buf = vec![0; 256];
+        buf[..21].copy_from_slice(b"JoyMax File Manager!\n");
+        buf[30..34].copy_from_slice(&0x0100_0002u32.to_le_bytes());
+        RawArchive { buf }
+    }
+
+    fn alloc_block(&mut self) -> u64 {
+        let offset = self.buf.len() as u64;
+        self.buf.resize(self.buf.len() + BLOCK_SIZE, 0);
+        offset
+    }
+
+    fn alloc_data(&mut self, data: &[u8]) -> u64 {
+        let offset = self.buf.len() as u64;
+        self.buf.extend_from_slice(data);
+        offset
+    }
+
+    fn set_entry(&mut self, block: u64, idx: usize, kind: u8, name: &str, pos: u64, size: u32) {
+        let at = block as usize + idx * 128;
+        let entry = &mut self.buf[at..at + 128];
+        entry[0] = kind;
+        entry[1..1 + name.len()].copy_from_slice(name.as_bytes());
+        entry[106..114].copy_from_slice(&pos.to_le_bytes());
+        entry[114..118].copy_from_slice(&size.to_le_bytes());
+    }
+
+    fn set_next_block(&mut self, block: u64, next: u64) {
+        let at = block as usize + 19 * 128 + 118;
+        self.buf[at..at + 8].copy_from_slice(&next.to_le_bytes());
+    }
+
+    fn open(self) -> Pk2<Cursor<Vec<u8>>> {
+        Pk2::open_in(Cursor::new(self.buf), "").unwrap()
+    }
+}
+
+fn sample() -> Pk2<Cursor<Vec<u8>>> {
+    let mut raw = RawArchive::new();
+    let root = raw.alloc_block();
+    let data = raw.alloc_block();
+    let readme = raw.alloc_data(b"hello pk2");
+    let blob = raw.alloc_data(&(0..=255).collect::<Vec<u8>>());
+    raw.set_entry(root, 0, 1, ".", root, 0);
+    raw.set_entry(root, 1, 1, "Data", data, 0);
+    raw.set_entry(root, 2, 2, "readme.txt", readme, 9);
+    raw.set_entry(data, 0, 1, ".", data, 0);
+    raw.set_entry(data, 1, 1, "..", root, 0);
+    raw.set_entry(data, 2, 2, "blob.bin", blob, 256);
+    raw.open()
+}
+
+#[test]
+fn read_file() {
+    let archive = sample();
+    let mut file = archive.open_file("/readme.txt").unwrap();
+    assert_eq!(file.name(), "readme.txt");
+    assert_eq!(file.len(), 9);
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "hello pk2");
+}
+
+#[test]
+fn lookup_is_case_insensitive_and_accepts_backslashes() {
+    let archive = sample();
+    assert_eq!(archive.open_file("DATA\\Blob.BIN").unwrap().len(), 256);
+    assert_eq!(archive.open_file("data/../data/./blob.bin").unwrap().len(), 256);
+}
+
+#[test]
+fn seek_and_read() {
+    let archive = sample();
+    let mut file = archive.open_file("data/blob.bin").unwrap();
+    let mut buf = [0; 4];
+    assert_eq!(file.seek(SeekFrom::Start(10)).unwrap(), 10);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [10, 11, 12, 13]);
+    assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 254);
+    assert_eq!(file.read(&mut buf).unwrap(), 2);
+    assert_eq!(buf[..2], [254, 255]);
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+    assert_eq!(file.seek(SeekFrom::Current(-256)).unwrap(), 0);
+    assert_eq!(file.seek(SeekFrom::Current(-1)).unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn missing_entries_and_directories() {
+    let archive = sample();
+    assert!(
+        matches!(archive.open_file("nope.txt"), Err(Error::NotFound(path)) if path == "nope.txt")
+    );
+    assert!(matches!(archive.open_file("readme.txt/x"), Err(Error::NotADirectory(_))));
+    assert!(matches!(archive.open_file("data"), Err(Error::NotAFile(_))));
+}
+
+#[test]
+fn follows_block_chain() {
+    let mut raw = RawArchive::new();
+    let root = raw.alloc_block();
+    let second = raw.alloc_block();
+    let payload = raw.alloc_data(b"x");
+    raw.set_entry(root, 0, 1, ".", root, 0);
+    raw.set_next_block(root, second);
+    raw.set_entry(second, 5, 2, "late.txt", payload, 1);
+    let archive = raw.open();
+    assert_eq!(archive.open_file("late.txt").unwrap().len(), 1);
+    let entries: Vec<_> = archive.read_dir("/").unwrap().map(Result::unwrap).collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].path.as_str(), entries[0].position), ("/late.txt", Some(payload)));
+}
+
+#[test]
+fn rejects_block_chain_cycles() {
+    let mut raw = RawArchive::new();
+    let root = raw.alloc_block();
+    raw.set_entry(root, 0, 1, ".", root, 0);
+    raw.set_next_block(root, root);
+    let archive = raw.open();
+    assert!(matches!(archive.open_file("x"), Err(Error::CorruptBlock { .. })));
+    let mut entries = archive.read_dir("").unwrap();
+    assert!(matches!(entries.next(), Some(Err(Error::CorruptBlock { offset: 256, .. }))));
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn rejects_foreign_files() {
+    let err = Pk2::open_in(Cursor::new(vec![0; 4096]), "").err().unwrap();
+    assert!(matches!(err, Error::InvalidSignature));
+}
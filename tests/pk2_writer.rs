@@ -0,0 +1,243 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use gfxfilemanager::pk2::blowfish::Blowfish;
+use gfxfilemanager::pk2::{FileTime, DEFAULT_PASSWORD};
+use gfxfilemanager::{Error, Pk2};
+
+fn reopen(archive: Pk2<Cursor<Vec<u8>>>, password: &str) -> Pk2<Cursor<Vec<u8>>> {
+    Pk2::open_in(archive.into_inner(), password).unwrap()
+}
+
+fn read_to_vec<B: Read + Seek>(archive: &Pk2<B>, path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    archive.open_file(path).unwrap().read_to_end(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn round_trip() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_directory("Media/server_dep/silkroad").unwrap();
+    archive.create_file("Media/type.txt").unwrap().write_all(b"Language = English").unwrap();
+    archive
+        .create_file("Media/server_dep/silkroad/gateway.dat")
+        .unwrap()
+        .write_all(&[1; 64])
+        .unwrap();
+    archive.create_file("empty.txt").unwrap();
+
+    let archive = reopen(archive, DEFAULT_PASSWORD);
+    assert_eq!(read_to_vec(&archive, "media/TYPE.txt"), b"Language = English");
+    assert_eq!(read_to_vec(&archive, "Media\\server_dep\\silkroad\\gateway.dat"), [1; 64]);
+    assert!(archive.open_file("empty.txt").unwrap().is_empty());
+}
+
+#[test]
+fn wrong_password() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), "secret").unwrap();
+    let err = Pk2::open_in(archive.into_inner(), DEFAULT_PASSWORD).err().unwrap();
+    assert!(matches!(err, Error::WrongPassword));
+    assert!(matches!(Pk2::create_in(Cursor::new(Vec::new()), ""), Err(Error::InvalidPassword)));
+}
+
+#[test]
+fn directories_grow_beyond_one_block() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    for i in 0..50 {
+        archive.create_file(&format!("dir/file{i}.bin")).unwrap().write_all(&[i as u8]).unwrap();
+    }
+    let archive = reopen(archive, DEFAULT_PASSWORD);
+    for i in 0..50 {
+        assert_eq!(read_to_vec(&archive, &format!("dir/file{i}.bin")), [i as u8]);
+    }
+}
+
+#[test]
+fn replace_file() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"first version").unwrap();
+    archive.create_file("A.TXT").unwrap().write_all(b"short").unwrap();
+    assert_eq!(read_to_vec(&archive, "a.txt"), b"short");
+    archive.create_file("a.txt").unwrap().write_all(b"a much longer version").unwrap();
+    assert_eq!(read_to_vec(&archive, "a.txt"), b"a much longer version");
+}
+
+#[test]
+fn seek_while_writing_and_file_times() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    let mut file = archive.create_file("data.bin").unwrap();
+    file.write_all(b"hello world").unwrap();
+    file.seek(SeekFrom::Start(6)).unwrap();
+    file.write_all(b"pk2!!").unwrap();
+    file.set_file_time(FileTime(1), FileTime(2));
+    file.flush().unwrap();
+    drop(file);
+
+    let archive = reopen(archive, DEFAULT_PASSWORD);
+    assert_eq!(read_to_vec(&archive, "data.bin"), b"hello pk2!!");
+    assert_eq!(archive.open_file("data.bin").unwrap().file_time(), (FileTime(1), FileTime(2)));
+}
+
+#[test]
+fn invalid_names_and_kinds() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    let long_name = "x".repeat(81);
+    assert!(matches!(archive.create_file(&long_name), Err(Error::NameTooLong(_))));
+    archive.create_directory("dir").unwrap();
+    assert!(matches!(archive.create_file("dir"), Err(Error::AlreadyExists(_))));
+    archive.create_file("file").unwrap();
+    assert!(matches!(archive.create_directory("file/sub"), Err(Error::AlreadyExists(_))));
+}
+
+#[test]
+fn create_on_disk() {
+    let path =
+        std::env::temp_dir().join(format!("gfxfilemanager-writer-{}.pk2", std::process::id()));
+    {
+        let archive = Pk2::create(&path, DEFAULT_PASSWORD).unwrap();
+        archive.create_file("readme.txt").unwrap().write_all(b"on disk").unwrap();
+    }
+    let archive = Pk2::open(&path, DEFAULT_PASSWORD).unwrap();
+    assert_eq!(read_to_vec(&archive, "readme.txt"), b"on disk");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rename_and_move() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("Media/textdata/item.txt").unwrap().write_all(b"item").unwrap();
+    archive.create_file("Media/textdata/skill.txt").unwrap().write_all(b"skill").unwrap();
+    archive.create_directory("Backup").unwrap();
+
+    archive.rename("media/textdata/item.txt", "media/textdata/ITEM.TXT").unwrap();
+    archive.rename("Media/textdata/skill.txt", "Media/skills.txt").unwrap();
+    archive.rename("Media/textdata", "Backup/text").unwrap();
+    let archive = reopen(archive, DEFAULT_PASSWORD);
+
+    assert_eq!(read_to_vec(&archive, "Backup/text/item.txt"), b"item");
+    assert_eq!(archive.find_files("Backup/text/*").unwrap()[0].name, "ITEM.TXT");
+    assert_eq!(read_to_vec(&archive, "Media/skills.txt"), b"skill");
+    assert!(matches!(archive.open_file("Media/textdata/item.txt"), Err(Error::NotFound(_))));
+    let mut archive = archive;
+    archive.change_directory("Backup/text").unwrap();
+    assert_eq!(read_to_vec(&archive, "item.txt"), b"item");
+    archive.reset_directory();
+
+    assert!(matches!(archive.rename("Backup", "Backup/text/inner"), Err(Error::InvalidPath(_))));
+    assert!(matches!(
+        archive.rename("Media/skills.txt", "Backup/text/item.txt"),
+        Err(Error::AlreadyExists(_))
+    ));
+    assert!(matches!(archive.rename("Media/missing", "Media/other"), Err(Error::NotFound(_))));
+    assert!(matches!(
+        archive.rename("Media/skills.txt", "Missing/skills.txt"),
+        Err(Error::NotFound(_))
+    ));
+    assert!(matches!(archive.rename("/", "Root"), Err(Error::InvalidPath(_))));
+}
+
+#[test]
+fn delete_tree_and_reuse_space() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    for i in 0..25 {
+        archive.create_file(&format!("Media/big/{i}.bin")).unwrap().write_all(&[i; 100]).unwrap();
+    }
+    archive.create_file("Media/big/deep/file.txt").unwrap().write_all(b"deep").unwrap();
+    archive.create_file("keep.txt").unwrap().write_all(b"keep").unwrap();
+    assert_eq!(archive.free_bytes(), 0);
+
+    assert!(matches!(archive.delete_directory("Media/big"), Err(Error::DirectoryNotEmpty(_))));
+    archive.delete_directory_all("media/big").unwrap();
+    // 25 payloads of 100 bytes, the deep file, two blocks of `big` and one of `deep`
+    assert_eq!(archive.free_bytes(), 2500 + 4 + 3 * 2560);
+    assert!(matches!(archive.open_file("Media/big/0.bin"), Err(Error::NotFound(_))));
+    assert!(matches!(archive.delete_directory_all("keep.txt"), Err(Error::NotADirectory(_))));
+
+    // new payloads and blocks are placed into the released space
+    let free = archive.free_regions();
+    archive.create_file("Media/new.bin").unwrap().write_all(&[7; 300]).unwrap();
+    archive.create_directory("Media/dir").unwrap();
+    assert_eq!(archive.free_bytes(), 2500 + 4 + 2 * 2560 - 300);
+    let new = archive.find_files("Media/new.bin").unwrap().remove(0);
+    let dir = archive.find_files("Media/dir").unwrap().remove(0);
+    for position in [new.position.unwrap(), dir.position.unwrap()] {
+        assert!(free.iter().any(|region| region.contains(&position)));
+    }
+
+    // replacing a file with a larger one releases the old payload
+    let before = archive.free_bytes();
+    archive.create_file("keep.txt").unwrap().write_all(&[1; 10_000]).unwrap();
+    assert_eq!(archive.free_bytes(), before + 4);
+    archive.delete_file("keep.txt").unwrap();
+    assert_eq!(archive.free_bytes(), before + 4 + 10_000);
+
+    let archive = reopen(archive, DEFAULT_PASSWORD);
+    assert_eq!(read_to_vec(&archive, "Media/new.bin"), [7; 300]);
+    assert_eq!(archive.read_dir("Media/dir").unwrap().count(), 0);
+    assert_eq!(archive.free_bytes(), 0);
+}
+
+#[test]
+fn open_files_keep_their_space() {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"first").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"other").unwrap();
+
+    // the payload of a deleted file is not handed out while it is still read
+    let mut deleted = archive.open_file("a.txt").unwrap();
+    let twice = archive.open_file("a.txt").unwrap();
+    archive.delete_file("a.txt").unwrap();
+    archive.create_file("c.txt").unwrap().write_all(b"third").unwrap();
+    assert_eq!(archive.free_bytes(), 0);
+    let mut buf = Vec::new();
+    deleted.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"first");
+    drop(deleted);
+    assert_eq!(archive.free_bytes(), 0);
+    drop(twice);
+    assert_eq!(archive.free_bytes(), 5);
+
+    // a replaced file is written elsewhere instead of in place
+    let old = archive.open_file("b.txt").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"new").unwrap();
+    let mut buf = [0; 5];
+    assert_eq!(old.read_at(&mut buf, 0).unwrap(), 5);
+    assert_eq!(&buf, b"other");
+    assert_eq!(archive.free_bytes(), 2);
+    drop(old);
+    assert_eq!(archive.free_bytes(), 5 + 5 - 3);
+    assert_eq!(read_to_vec(&archive, "b.txt"), b"new");
+}
+
+/// Decrypts the block at `offset` of a container created with the default password.
+fn decrypted_block(buf: &[u8], offset: usize) -> Vec<u8> {
+    let mut block = buf[offset..offset + 2560].to_vec();
+    Blowfish::from_password(DEFAULT_PASSWORD).unwrap().decrypt(&mut block).unwrap();
+    block
+}
+
+#[test]
+fn names_in_other_encodings_are_kept() {
+    const EUC_KR: [u8; 4] = [0xc7, 0xd1, b'.', b't'];
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("ab.t").unwrap().write_all(b"old").unwrap();
+    archive.create_directory("dir").unwrap();
+    let mut buf = archive.into_inner().into_inner();
+    let mut block = decrypted_block(&buf, 256);
+    let at = block.windows(5).position(|w| w == b"ab.t\0").unwrap();
+    block[at..at + 4].copy_from_slice(&EUC_KR);
+    Blowfish::from_password(DEFAULT_PASSWORD).unwrap().encrypt(&mut block).unwrap();
+    buf[256..256 + 2560].copy_from_slice(&block);
+
+    // rewriting the file and moving it under the same name keeps the stored bytes
+    let archive = Pk2::open_in(Cursor::new(buf), DEFAULT_PASSWORD).unwrap();
+    let name = "\u{fffd}\u{fffd}.t";
+    archive.create_file(name).unwrap().write_all(b"new").unwrap();
+    archive.rename(name, &format!("dir/{name}")).unwrap();
+    let dir = archive.find_files("dir").unwrap().remove(0).position.unwrap() as usize;
+    let buf = archive.into_inner().into_inner();
+    let stored = [&[2][..], &EUC_KR, &[0]].concat();
+    assert!(decrypted_block(&buf, dir).windows(6).any(|w| w == stored));
+    let archive = Pk2::open_in(Cursor::new(buf), DEFAULT_PASSWORD).unwrap();
+    assert_eq!(read_to_vec(&archive, &format!("dir/{name}")), b"new");
+}
@@ -0,0 +1,38 @@
+use std::io::Cursor;
+
+use gfxfilemanager::pk2::recover::{KeySpace, PasswordRecovery};
+use gfxfilemanager::pk2::DEFAULT_PASSWORD;
+use gfxfilemanager::Pk2;
+
+fn container(password: &str) -> Cursor<Vec<u8>> {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), password).unwrap();
+    archive.into_inner()
+}
+
+#[test]
+fn wordlist() {
+    let recovery = PasswordRecovery::new(container("hunter2")).unwrap();
+    let words = ["", "password", DEFAULT_PASSWORD, "hunter2", "hunter3"];
+    assert_eq!(recovery.search(words).collect::<Vec<_>>(), ["hunter2"]);
+    assert!(recovery.check("hunter2"));
+    assert!(!recovery.check(""));
+}
+
+#[test]
+fn key_space() {
+    let recovery = PasswordRecovery::new(container("b1a")).unwrap();
+    let key_space = KeySpace::new("ab1", 2, 3);
+    assert_eq!(key_space.remaining(), 9 + 27);
+    assert_eq!(recovery.search(key_space).collect::<Vec<_>>(), ["b1a"]);
+}
+
+#[test]
+fn key_space_order() {
+    let mut key_space = KeySpace::new("xyy", 0, 2);
+    assert_eq!(key_space.remaining(), 6);
+    assert_eq!(key_space.next().unwrap(), "x");
+    assert_eq!(key_space.remaining(), 5);
+    assert_eq!(key_space.collect::<Vec<_>>(), ["y", "xx", "xy", "yx", "yy"]);
+    assert_eq!(KeySpace::new("", 1, 4).count(), 0);
+    assert_eq!(KeySpace::new("ab", 3, 2).remaining(), 0);
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+
+use gfxfilemanager::pk2::blowfish::Blowfish;
+use gfxfilemanager::pk2::{RepairSummary, DEFAULT_PASSWORD};
+use gfxfilemanager::{FileManager, Pk2, WalkEvent};
+
+const ROOT_BLOCK: usize = 256;
+const BLOCK_SIZE: usize = 2560;
+const ENTRY_SIZE: usize = 128;
+
+fn archive() -> Pk2<Cursor<Vec<u8>>> {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"first file").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"second file").unwrap();
+    for i in 0..25 {
+        let path = format!("sub/deep/{i}.bin");
+        archive.create_file(&path).unwrap().write_all(&vec![i as u8; 300 + i]).unwrap();
+    }
+    archive.create_file("sub/c.txt").unwrap().write_all(b"third file").unwrap();
+    archive
+}
+
+fn contents<B: std::io::Read + std::io::Seek>(archive: &Pk2<B>) -> BTreeMap<String, Vec<u8>>
+where
+    Pk2<B>: FileManager,
+{
+    archive
+        .walk("/")
+        .filter_map(|event| match event.unwrap() {
+            WalkEvent::File(file) => Some(file),
+            _ => None,
+        })
+        .map(|file| (file.path.clone(), archive.bytes(&file.path).unwrap()))
+        .collect()
+}
+
+/// Decrypts the block at `offset`, lets `patch` modify it and encrypts it again.
+fn patch_block<T>(buf: &mut [u8], offset: usize, patch: impl FnOnce(&mut [u8]) -> T) -> T {
+    let blowfish = Blowfish::from_password(DEFAULT_PASSWORD).unwrap();
+    let block = &mut buf[offset..offset + BLOCK_SIZE];
+    blowfish.decrypt(block).unwrap();
+    let result = patch(block);
+    blowfish.encrypt(block).unwrap();
+    result
+}
+
+/// Returns the position of the entry with the given name in the block at `offset`.
+fn position(buf: &mut [u8], offset: usize, name: &str) -> usize {
+    patch_block(buf, offset, |block| {
+        u64::from_le_bytes(slot(block, name)[106..114].try_into().unwrap()) as usize
+    })
+}
+
+fn slot<'a>(block: &'a mut [u8], name: &str) -> &'a mut [u8] {
+    block
+        .chunks_exact_mut(ENTRY_SIZE)
+        .find(|slot| slot[1..].starts_with(name.as_bytes()) && slot[1 + name.len()] == 0)
+        .unwrap()
+}
+
+fn repair(buf: Vec<u8>) -> (Pk2<Cursor<Vec<u8>>>, RepairSummary) {
+    let damaged = Pk2::open_in(Cursor::new(buf), DEFAULT_PASSWORD).unwrap();
+    let mut out = Vec::new();
+    let summary = damaged.repair_to(Cursor::new(&mut out)).unwrap();
+    let repaired = Pk2::open_in(Cursor::new(out), DEFAULT_PASSWORD).unwrap();
+    let report = repaired.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    (repaired, summary)
+}
+
+#[test]
+fn intact_containers_are_copied() {
+    let archive = archive();
+    let expected = contents(&archive);
+    let (repaired, summary) = repair(archive.into_inner().into_inner());
+    assert_eq!(contents(&repaired), expected);
+    assert_eq!((summary.directories, summary.files, summary.orphans), (2, 28, 0));
+    assert_eq!((summary.blocks, summary.dropped), (4, 0));
+}
+
+#[test]
+fn orphans_go_to_lost_and_found() {
+    let mut buf = archive().into_inner().into_inner();
+    let sub = position(&mut buf, ROOT_BLOCK, "sub");
+    patch_block(&mut buf, ROOT_BLOCK, |block| slot(block, "sub")[0] = 9);
+
+    let (repaired, summary) = repair(buf);
+    assert_eq!(summary.orphans, 1);
+    assert_eq!(summary.dropped, 1);
+    let lost = format!("/lost+found/{sub:#x}");
+    assert_eq!(repaired.bytes("a.txt").unwrap(), b"first file");
+    assert_eq!(repaired.bytes(&format!("{lost}/c.txt")).unwrap(), b"third file");
+    assert_eq!(repaired.bytes(&format!("{lost}/deep/24.bin")).unwrap(), vec![24; 324]);
+    assert_eq!(repaired.read_dir(&format!("{lost}/deep")).unwrap().count(), 25);
+}
+
+#[test]
+fn unreadable_root_and_broken_payloads() {
+    let mut buf = archive().into_inner().into_inner();
+    let sub = position(&mut buf, ROOT_BLOCK, "sub");
+    let deep = position(&mut buf, sub, "deep");
+    // the root block turns into garbage, its files are lost but the directories below are not
+    buf[ROOT_BLOCK..ROOT_BLOCK + BLOCK_SIZE].fill(0xaa);
+
+    let (repaired, summary) = repair(buf.clone());
+    assert!(repaired.read_dir("/").unwrap().all(|e| e.unwrap().name == "lost+found"));
+    assert_eq!(summary.orphans, 1);
+    let sub = repaired.read_dir("lost+found").unwrap().next().unwrap().unwrap().name;
+    assert_eq!(repaired.bytes(&format!("lost+found/{sub}/c.txt")).unwrap(), b"third file");
+    assert_eq!(repaired.read_dir(&format!("lost+found/{sub}/deep")).unwrap().count(), 25);
+
+    // a payload past the end is dropped and a broken chain keeps the blocks before it
+    patch_block(&mut buf, deep, |block| {
+        slot(block, "0.bin")[114..118].copy_from_slice(&u32::MAX.to_le_bytes());
+        block[BLOCK_SIZE - 10..BLOCK_SIZE - 2].copy_from_slice(&u64::MAX.to_le_bytes());
+    });
+    let (repaired, summary) = repair(buf);
+    let sub = repaired.read_dir("lost+found").unwrap().next().unwrap().unwrap().name;
+    // 0.bin is dropped and 18.bin to 24.bin were in the lost second block
+    assert_eq!(repaired.read_dir(&format!("lost+found/{sub}/deep")).unwrap().count(), 17);
+    assert!(summary.dropped >= 1);
+}
+
+#[test]
+fn self_links_with_leftover_name_bytes() {
+    let archive = archive();
+    let big: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+    archive.create_file("sub/big.bin").unwrap().write_all(&big).unwrap();
+    let expected = contents(&archive);
+    let mut buf = archive.into_inner().into_inner();
+    let sub = position(&mut buf, ROOT_BLOCK, "sub");
+    let deep = position(&mut buf, sub, "deep");
+    // garbage after the null byte of the `.` names, the root is aligned and the other
+    // directories are only linked
+    for offset in [ROOT_BLOCK, sub, deep] {
+        patch_block(&mut buf, offset, |block| block[3..20].fill(0x5a));
+    }
+
+    let (repaired, summary) = repair(buf);
+    assert_eq!(contents(&repaired), expected);
+    assert_eq!((summary.directories, summary.files, summary.orphans), (2, 29, 0));
+    assert_eq!(repaired.bytes("sub/big.bin").unwrap(), big);
+}
+
+#[test]
+fn names_in_other_encodings_are_kept() {
+    let mut buf = archive().into_inner().into_inner();
+    // a long name whose decoded form would be too long, a short one and two names that only
+    // differ in bytes that decode to the same replacement character
+    let names: [&[u8]; 4] = [&[0xe9; 30], &[0xc7, 0xd1, b'.', b't'], &[0xfe, b'x'], &[0xff, b'x']];
+    let sub = position(&mut buf, ROOT_BLOCK, "sub");
+    patch_block(&mut buf, ROOT_BLOCK, |block| {
+        for (old, new) in ["a.txt", "b.txt"].into_iter().zip(&names) {
+            let slot = slot(block, old);
+            slot[1..82].fill(0);
+            slot[1..1 + new.len()].copy_from_slice(new);
+        }
+    });
+    patch_block(&mut buf, sub, |block| {
+        let copy = slot(block, "c.txt").to_vec();
+        for (old, new) in ["c.txt", "deep"].into_iter().zip(&names[2..]) {
+            let slot = slot(block, old);
+            slot.copy_from_slice(&copy);
+            slot[1..82].fill(0);
+            slot[1..1 + new.len()].copy_from_slice(new);
+        }
+    });
+
+    let (repaired, summary) = repair(buf);
+    // the files of `deep` are kept in lost+found, its entry was overwritten
+    assert_eq!((summary.files, summary.orphans, summary.dropped), (29, 1, 0));
+    let buf = repaired.into_inner().into_inner();
+    let stored = |offset: usize, name: &[u8]| {
+        let mut buf = buf.clone();
+        patch_block(&mut buf, offset, |block| {
+            block
+                .chunks_exact(ENTRY_SIZE)
+                .any(|slot| slot[1..].starts_with(name) && slot[1 + name.len()] == 0)
+        })
+    };
+    assert!(stored(ROOT_BLOCK, names[0]) && stored(ROOT_BLOCK, names[1]));
+    let mut copy = buf.clone();
+    let sub = position(&mut copy, ROOT_BLOCK, "sub");
+    assert!(stored(sub, names[2]) && stored(sub, names[3]));
+    let repaired = Pk2::open_in(Cursor::new(buf), DEFAULT_PASSWORD).unwrap();
+    let name = String::from_utf8_lossy(names[1]);
+    assert_eq!(repaired.bytes(&name).unwrap(), b"second file");
+}
@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+
+use gfxfilemanager::pk2::blowfish::Blowfish;
+use gfxfilemanager::pk2::{ProblemKind, DEFAULT_PASSWORD};
+use gfxfilemanager::Pk2;
+
+const ROOT_BLOCK: usize = 256;
+const BLOCK_SIZE: usize = 2560;
+const ENTRY_SIZE: usize = 128;
+
+fn archive() -> Pk2<Cursor<Vec<u8>>> {
+    let archive = Pk2::create_in(Cursor::new(Vec::new()), DEFAULT_PASSWORD).unwrap();
+    archive.create_file("a.txt").unwrap().write_all(b"first file").unwrap();
+    archive.create_file("b.txt").unwrap().write_all(b"second file").unwrap();
+    archive.create_file("sub/c.txt").unwrap().write_all(b"third file").unwrap();
+    archive
+}
+
+/// Decrypts the root block, lets `patch` modify the slot with the given name and encrypts it
+/// again.
+fn patch_root(buf: &mut [u8], name: &str, patch: impl FnOnce(&mut [u8])) {
+    let blowfish = Blowfish::from_password(DEFAULT_PASSWORD).unwrap();
+    let block = &mut buf[ROOT_BLOCK..ROOT_BLOCK + BLOCK_SIZE];
+    blowfish.decrypt(block).unwrap();
+    let slot = block
+        .chunks_exact_mut(ENTRY_SIZE)
+        .find(|slot| slot[1..].starts_with(name.as_bytes()) && slot[1 + name.len()] == 0)
+        .unwrap();
+    patch(slot);
+    blowfish.encrypt(block).unwrap();
+}
+
+fn kinds(archive: Pk2<Cursor<Vec<u8>>>) -> HashSet<ProblemKind> {
+    let report = archive.verify().unwrap();
+    report.problems.iter().map(|problem| problem.kind).collect()
+}
+
+fn corrupt(patch: impl FnOnce(&mut Vec<u8>)) -> Pk2<Cursor<Vec<u8>>> {
+    let mut buf = archive().into_inner().into_inner();
+    patch(&mut buf);
+    Pk2::open_in(Cursor::new(buf), DEFAULT_PASSWORD).unwrap()
+}
+
+#[test]
+fn healthy_containers_pass() {
+    let archive = archive();
+    archive.create_file("a.txt").unwrap().write_all(&[7; 5000]).unwrap();
+    archive.rename("sub", "moved").unwrap();
+    for i in 0..30 {
+        archive.create_file(&format!("moved/{i}.bin")).unwrap().write_all(&[i; 100]).unwrap();
+    }
+    archive.delete_file("b.txt").unwrap();
+
+    let report = archive.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!((report.directories, report.files, report.blocks), (2, 32, 3));
+    assert_eq!(report.container_len, archive.into_inner().into_inner().len() as u64);
+    assert_eq!(
+        report.to_json(),
+        format!(
+            r#"{{"ok":true,"container_len":{},"blocks":3,"directories":2,"files":32,"problems":[]}}"#,
+            report.container_len
+        )
+    );
+}
+
+#[test]
+fn reports_broken_entries() {
+    let archive = corrupt(|buf| {
+        patch_root(buf, "a.txt", |slot| slot[0] = 7);
+        patch_root(buf, "b.txt", |slot| slot[114..118].copy_from_slice(&u32::MAX.to_le_bytes()));
+        patch_root(buf, ".", |slot| slot[0] = 0);
+    });
+    let report = archive.verify().unwrap();
+    assert_eq!(
+        kinds(archive),
+        HashSet::from([
+            ProblemKind::InvalidEntryType,
+            ProblemKind::PayloadPastEof,
+            ProblemKind::MissingSelfLink
+        ])
+    );
+    let past_eof = report.problems.iter().find(|p| p.kind == ProblemKind::PayloadPastEof).unwrap();
+    assert_eq!(past_eof.path, "/b.txt");
+    assert!(report.to_json().contains(r#""code":"payload-past-eof","path":"/b.txt""#));
+}
+
+#[test]
+fn reports_overlaps_and_names() {
+    let archive = corrupt(|buf| {
+        // b.txt points into the payload of a.txt, c.txt in sub is still fine
+        patch_root(buf, "b.txt", |slot| {
+            let position = u64::from_le_bytes(slot[106..114].try_into().unwrap());
+            slot[106..114].copy_from_slice(&(position - 4).to_le_bytes());
+        });
+        patch_root(buf, "a.txt", |slot| slot[2] = b'/');
+    });
+    let report = archive.verify().unwrap();
+    assert_eq!(report.files, 3);
+    let overlap = report.problems.iter().find(|p| p.kind == ProblemKind::Overlap).unwrap();
+    assert_eq!(overlap.path, "/b.txt");
+    assert_eq!(overlap.message, "overlaps the payload of /a/txt");
+    assert_eq!(kinds(archive), HashSet::from([ProblemKind::Overlap, ProblemKind::InvalidName]));
+
+    let archive = corrupt(|buf| {
+        patch_root(buf, "a.txt", |slot| slot[106..114].copy_from_slice(&100u64.to_le_bytes()));
+        patch_root(buf, "b.txt", |slot| slot[1] = b'A');
+    });
+    assert_eq!(kinds(archive), HashSet::from([ProblemKind::Overlap, ProblemKind::DuplicateName]));
+
+    // names that are not valid UTF-8 are kept as they are and not reported
+    let archive = corrupt(|buf| patch_root(buf, "a.txt", |slot| slot[1] = 0xff));
+    assert!(archive.verify().unwrap().is_ok());
+
+    // the length is that of the stored bytes, each of which would decode to three
+    let archive = corrupt(|buf| {
+        patch_root(buf, "a.txt", |slot| {
+            slot[1..31].fill(0xe9);
+            slot[31] = 0;
+        })
+    });
+    let report = archive.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+}
+
+#[test]
+fn reports_overlapping_blocks_with_their_directory() {
+    for (position, other) in [(128, "the header"), (ROOT_BLOCK + 128, "a block of /")] {
+        let archive = corrupt(|buf| {
+            patch_root(buf, "sub", |slot| {
+                slot[106..114].copy_from_slice(&(position as u64).to_le_bytes())
+            });
+        });
+        let report = archive.verify().unwrap();
+        let overlaps: Vec<_> =
+            report.problems.iter().filter(|p| p.kind == ProblemKind::Overlap).collect();
+        // the blocks are reported with the path of their directory
+        assert!(overlaps.iter().all(|p| p.path.starts_with('/')), "{overlaps:?}");
+        let sub = overlaps.iter().find(|p| p.path == "/sub").unwrap();
+        assert_eq!(sub.message, format!("overlaps {other}"));
+        assert_eq!(sub.offset, position as u64);
+    }
+}
+
+#[test]
+fn reports_cycles_and_wrong_links() {
+    // the directory points back to the root, whose blocks are not read twice
+    let archive = corrupt(|buf| {
+        patch_root(buf, "sub", |slot| {
+            slot[106..114].copy_from_slice(&(ROOT_BLOCK as u64).to_le_bytes())
+        });
+    });
+    assert_eq!(kinds(archive), HashSet::from([ProblemKind::SharedBlock]));
+
+    let archive = corrupt(|buf| {
+        patch_root(buf, "..", |slot| slot[106..114].copy_from_slice(&1234u64.to_le_bytes()));
+        let blowfish = Blowfish::from_password(DEFAULT_PASSWORD).unwrap();
+        let block = &mut buf[ROOT_BLOCK..ROOT_BLOCK + BLOCK_SIZE];
+        blowfish.decrypt(block).unwrap();
+        block[BLOCK_SIZE - 10..BLOCK_SIZE - 2].copy_from_slice(&(ROOT_BLOCK as u64).to_le_bytes());
+        blowfish.encrypt(block).unwrap();
+    });
+    assert_eq!(kinds(archive), HashSet::from([ProblemKind::ChainCycle, ProblemKind::WrongLink]));
+
+    let archive = corrupt(|buf| {
+        patch_root(buf, "sub", |slot| slot[106..114].copy_from_slice(&(1u64 << 40).to_le_bytes()));
+    });
+    let report = archive.verify().unwrap();
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(report.problems[0].kind, ProblemKind::BlockPastEof);
+    assert_eq!(report.problems[0].path, "/sub");
+}